@@ -0,0 +1,18 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+fn read(c: &mut Criterion) {
+    // A single PPI sweep of 360 rays with 1000 gates of four moments each
+    let bytes = rhix_converter::SyntheticScan::default().to_bytes();
+    c.bench_function("read_rhix 360x1000x4", |b| {
+        b.iter(|| rhix_converter::read_rhix(black_box(&bytes)).unwrap())
+    });
+
+    let mut parser = rhix_converter::Parser::new();
+    let options = rhix_converter::ReadOptions::default();
+    c.bench_function("Parser::read 360x1000x4", |b| {
+        b.iter(|| parser.read(black_box(&bytes[..]), &options).unwrap())
+    });
+}
+
+criterion_group!(benches, read);
+criterion_main!(benches);
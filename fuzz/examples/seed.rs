@@ -0,0 +1,50 @@
+//! Writes synthetic scans to `corpus/read` as a starting corpus for the `read` target.
+
+use rhix_converter::{Endian, Moment, SyntheticScan};
+
+fn main() -> std::io::Result<()> {
+    let small = SyntheticScan {
+        rays: 8,
+        gates: 16,
+        ..Default::default()
+    };
+    let seeds = [
+        ("small", small.clone()),
+        (
+            "big_endian",
+            SyntheticScan {
+                endian: Endian::Big,
+                ..small.clone()
+            },
+        ),
+        (
+            "all_moments",
+            SyntheticScan {
+                moments: Moment::ALL.to_vec(),
+                ..small.clone()
+            },
+        ),
+        (
+            "one_moment",
+            SyntheticScan {
+                moments: vec![Moment::Reflectivity],
+                ..small.clone()
+            },
+        ),
+        (
+            "no_gates",
+            SyntheticScan {
+                gates: 0,
+                ..small.clone()
+            },
+        ),
+        ("no_rays", SyntheticScan { rays: 0, ..small }),
+    ];
+
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus/read");
+    std::fs::create_dir_all(&dir)?;
+    for (name, scan) in seeds {
+        std::fs::write(dir.join(format!("{name}.rhix")), scan.to_bytes())?;
+    }
+    Ok(())
+}
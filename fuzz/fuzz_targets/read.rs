@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rhix_converter::{Endian, ReadOptions};
+
+// Any input may fail to parse, but never panic
+fuzz_target!(|data: &[u8]| {
+    for endian in [Endian::Little, Endian::Big] {
+        let _ = rhix_converter::observation_mode(data, endian);
+        for strict in [false, true] {
+            let options = ReadOptions {
+                endian,
+                strict,
+                ..Default::default()
+            };
+            let _ = rhix_converter::read_rhix_from_reader(data, &options);
+        }
+    }
+});
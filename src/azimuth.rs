@@ -0,0 +1,115 @@
+/// Reference and direction of ray azimuths in the output.
+///
+/// WR2120 files store PPI azimuths in degrees clockwise from north, after the header's antenna
+/// offset brings them to true north, which is the meteorological convention kept by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AzimuthConvention {
+    /// 0 is north and angles increase clockwise, so 90 is east.
+    Meteorological,
+    /// 0 is east and angles increase counterclockwise, so 90 is north.
+    Mathematical,
+}
+
+impl AzimuthConvention {
+    /// Converts a meteorological azimuth in degrees to this convention, in `[0, 360)`.
+    pub fn convert(self, azimuth: f32) -> f32 {
+        match self {
+            AzimuthConvention::Meteorological => azimuth.rem_euclid(360.0),
+            AzimuthConvention::Mathematical => (90.0 - azimuth).rem_euclid(360.0),
+        }
+    }
+}
+
+impl std::str::FromStr for AzimuthConvention {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "meteorological" => Ok(AzimuthConvention::Meteorological),
+            "mathematical" => Ok(AzimuthConvention::Mathematical),
+            _ => Err(format!(
+                "Unknown azimuth convention [{s}], expected meteorological or mathematical"
+            )),
+        }
+    }
+}
+
+/// Sorts the rays of a sweep by increasing azimuth.
+pub fn sort_azimuth(sweep: &mut silv::Sweep) {
+    sweep.rays.sort_by(|a, b| a.azimuth.total_cmp(&b.azimuth));
+}
+
+/// Replaces the rays of a sweep with `bins` rays evenly spaced around the full circle.
+///
+/// Bin `i` is centered on `(i + 0.5) * 360 / bins` degrees and takes the data and time of the
+/// ray nearest to that azimuth, so rays may be repeated where the sweep is sparse or dropped
+/// where it is dense. Only meaningful for PPI sweeps.
+pub fn resample_azimuth(sweep: &mut silv::Sweep, bins: usize) {
+    if bins == 0 || sweep.rays.is_empty() {
+        return;
+    }
+
+    let distance = |a: f32, b: f32| {
+        let d = (a - b).rem_euclid(360.0);
+        d.min(360.0 - d)
+    };
+
+    let width = 360.0 / bins as f32;
+    let rays = (0..bins)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * width;
+            let nearest = sweep
+                .rays
+                .iter()
+                .min_by(|a, b| distance(a.azimuth, center).total_cmp(&distance(b.azimuth, center)))
+                .unwrap();
+
+            silv::Ray {
+                azimuth: center,
+                time: nearest.time,
+                data: nearest.data.clone(),
+            }
+        })
+        .collect();
+
+    sweep.rays = rays;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conventions_convert_known_angles() {
+        let meteorological =
+            [0.0, 90.0, 370.0, -10.0].map(|a| AzimuthConvention::Meteorological.convert(a));
+        assert_eq!(meteorological, [0.0, 90.0, 10.0, 350.0]);
+        let mathematical =
+            [0.0, 90.0, 180.0, 270.0].map(|a| AzimuthConvention::Mathematical.convert(a));
+        assert_eq!(mathematical, [90.0, 0.0, 270.0, 180.0]);
+    }
+
+    #[test]
+    fn rhi_angles_are_measured_from_the_zenith() {
+        let scan = crate::SyntheticScan {
+            rays: 2,
+            gates: 10,
+            elevation: 30.0,
+            observation_mode: 2,
+            ..Default::default()
+        };
+        let bytes = scan.to_bytes();
+
+        for (convention, azimuth) in [
+            (AzimuthConvention::Meteorological, 60.0),
+            (AzimuthConvention::Mathematical, 30.0),
+        ] {
+            let options = crate::ReadOptions {
+                azimuth_convention: convention,
+                ..Default::default()
+            };
+            let (_, radar) = crate::read_rhix_with(&bytes, &options).unwrap();
+            assert_eq!(radar.sweeps[0].rays[0].azimuth, azimuth);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use crate::{ObservationMode, RhixHeader};
+
+/// Writes a CfRadial-1 NetCDF file with one ray per `time` step.
+///
+/// Fields are stored as `f32` on a `(time, range)` grid, with missing gates and the padding
+/// where a ray has fewer gates than the longest one set to `fill_value`, NaN when `None`.
+/// Every sweep gets the `sweep_mode` of the header's observation mode, and the site altitude
+/// is the header's.
+pub fn write_cfradial(
+    radar: &silv::RadarFile,
+    header: &RhixHeader,
+    fill_value: Option<f64>,
+    path: impl AsRef<std::path::Path>,
+) -> Result<(), netcdf::Error> {
+    let fill = fill_value.map_or(f32::NAN, |fill| fill as f32);
+    let rays: Vec<_> = radar.sweeps.iter().flat_map(|s| &s.rays).collect();
+    let gates = rays
+        .iter()
+        .flat_map(|r| r.data.values().map(Vec::len))
+        .max()
+        .unwrap_or(0);
+
+    let mut moments: Vec<_> = radar.params.keys().collect();
+    moments.sort();
+
+    let mut file = netcdf::create(path)?;
+    file.add_attribute("Conventions", "CF/Radial")?;
+    file.add_attribute("version", "1.3")?;
+    file.add_attribute("instrument_name", radar.name.as_str())?;
+
+    file.add_dimension("time", rays.len())?;
+    file.add_dimension("range", gates)?;
+    file.add_dimension("sweep", radar.sweeps.len())?;
+
+    if let Some(site) = radar.sweeps.first() {
+        for (name, value, units) in [
+            ("latitude", site.latitude, "degrees_north"),
+            ("longitude", site.longitude, "degrees_east"),
+            ("altitude", header.site.altitude as f32, "meters"),
+        ] {
+            let mut var = file.add_variable::<f64>(name, &[])?;
+            var.put_attribute("units", units)?;
+            var.put_value(value as f64, ..)?;
+        }
+    }
+
+    if let Some(first) = rays.first() {
+        let start = first.time;
+        let times: Vec<f64> = rays
+            .iter()
+            .map(|r| (r.time - start).num_milliseconds() as f64 / 1000.0)
+            .collect();
+
+        let mut var = file.add_variable::<f64>("time", &["time"])?;
+        var.put_attribute("standard_name", "time")?;
+        let units = format!("seconds since {}", start.format("%Y-%m-%dT%H:%M:%SZ"));
+        var.put_attribute("units", units)?;
+        var.put_values(&times, ..)?;
+    }
+
+    if let Some(param) = moments.first().map(|name| &radar.params[name.as_str()]) {
+        let ranges = crate::gate_ranges(param, gates);
+
+        let mut var = file.add_variable::<f32>("range", &["range"])?;
+        var.put_attribute("units", "meters")?;
+        let first = ranges
+            .first()
+            .copied()
+            .unwrap_or(param.meters_to_first_cell);
+        var.put_attribute("meters_to_center_of_first_gate", first)?;
+        var.put_attribute("meters_between_gates", param.meters_between_cells)?;
+        var.put_values(&ranges, ..)?;
+    }
+
+    let azimuths: Vec<f32> = rays.iter().map(|r| r.azimuth).collect();
+    let mut var = file.add_variable::<f32>("azimuth", &["time"])?;
+    var.put_attribute("units", "degrees")?;
+    var.put_values(&azimuths, ..)?;
+
+    let elevations: Vec<f32> = radar
+        .sweeps
+        .iter()
+        .flat_map(|s| s.rays.iter().map(|_| s.elevation))
+        .collect();
+    let mut var = file.add_variable::<f32>("elevation", &["time"])?;
+    var.put_attribute("units", "degrees")?;
+    var.put_values(&elevations, ..)?;
+
+    let mut start_index = Vec::new();
+    let mut end_index = Vec::new();
+    let mut index = 0;
+    for sweep in &radar.sweeps {
+        start_index.push(index);
+        index += sweep.rays.len() as i32;
+        end_index.push(index - 1);
+    }
+
+    let sweep_numbers: Vec<i32> = (0..radar.sweeps.len() as i32).collect();
+    let fixed_angles: Vec<f32> = radar.sweeps.iter().map(|s| s.elevation).collect();
+    let nyquist: Vec<f32> = radar
+        .sweeps
+        .iter()
+        .flat_map(|s| s.rays.iter().map(|_| s.nyquist_velocity))
+        .collect();
+
+    file.add_variable::<i32>("sweep_number", &["sweep"])?
+        .put_values(&sweep_numbers, ..)?;
+    file.add_variable::<f32>("fixed_angle", &["sweep"])?
+        .put_values(&fixed_angles, ..)?;
+    file.add_variable::<i32>("sweep_start_ray_index", &["sweep"])?
+        .put_values(&start_index, ..)?;
+    file.add_variable::<i32>("sweep_end_ray_index", &["sweep"])?
+        .put_values(&end_index, ..)?;
+    file.add_variable::<f32>("nyquist_velocity", &["time"])?
+        .put_values(&nyquist, ..)?;
+
+    let mut var = file.add_string_variable("sweep_mode", &["sweep"])?;
+    for i in 0..radar.sweeps.len() {
+        var.put_string(sweep_mode(header.geometry.observation_mode), [i])?;
+    }
+
+    for name in moments {
+        let param = &radar.params[name.as_str()];
+        let mut values = vec![fill; rays.len() * gates];
+        for (row, ray) in values.chunks_exact_mut(gates.max(1)).zip(&rays) {
+            if let Some(data) = ray.data.get(name.as_str()) {
+                for (v, d) in row.iter_mut().zip(data).filter(|(_, d)| !d.is_nan()) {
+                    *v = *d as f32;
+                }
+            }
+        }
+
+        let mut var = file.add_variable::<f32>(name, &["time", "range"])?;
+        var.put_attribute("long_name", param.description.as_str())?;
+        var.put_attribute("units", param.units.as_str())?;
+        var.put_attribute("_FillValue", fill)?;
+        var.put_attribute("coordinates", "time range")?;
+        var.put_values(&values, ..)?;
+    }
+
+    Ok(())
+}
+
+/// CfRadial `sweep_mode` of a WR2120 observation mode.
+///
+/// PPI codes 1, 3 and 4 are all `azimuth_surveillance` and RHI code 2 is `rhi`. Unknown codes
+/// are parsed like an RHI, so they are `manual_rhi`.
+fn sweep_mode(mode: ObservationMode) -> &'static str {
+    match mode {
+        ObservationMode::Ppi => "azimuth_surveillance",
+        ObservationMode::Rhi => "rhi",
+        ObservationMode::Unknown(_) => "manual_rhi",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_mode_code_has_a_sweep_mode() {
+        let scan = crate::SyntheticScan {
+            rays: 2,
+            gates: 10,
+            ..Default::default()
+        };
+        for (code, mode) in [
+            (1, "azimuth_surveillance"),
+            (2, "rhi"),
+            (3, "azimuth_surveillance"),
+            (4, "azimuth_surveillance"),
+            (5, "manual_rhi"),
+        ] {
+            let scan = crate::SyntheticScan {
+                observation_mode: code,
+                ..scan.clone()
+            };
+            let (header, _) = crate::read_rhix(&scan.to_bytes()).unwrap();
+            assert_eq!(sweep_mode(header.geometry.observation_mode), mode, "{code}");
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use crate::Moment;
+
+/// Unfolds the "VEL" moment of a sweep with a gate-to-gate continuity pass.
+///
+/// Each gate is shifted by whole Nyquist intervals (`2 * nyquist`) to land as close as possible
+/// to the previous valid gate along the ray. The first valid gate of a ray is compared against
+/// the first valid gate of the previous ray, and the very first gate of the sweep is assumed to
+/// be unfolded.
+///
+/// This only works when the true velocity changes by less than the Nyquist velocity between
+/// neighbouring gates. Noisy data, isolated echoes and real shear larger than the interval can
+/// throw off the reference, and the error then carries along the rest of the ray.
+pub fn dealias(sweep: &mut silv::Sweep, nyquist: f32) {
+    let interval = 2.0 * nyquist as f64;
+    if interval <= 0.0 {
+        return;
+    }
+
+    let unfold = |v: f64, reference: f64| v + ((reference - v) / interval).round() * interval;
+
+    // First valid gate of the previous ray, after unfolding
+    let mut ray_start = None;
+    for ray in &mut sweep.rays {
+        let Some(vel) = ray.data.get_mut(Moment::Velocity.name()) else {
+            continue;
+        };
+
+        let mut reference = ray_start;
+        for (i, v) in vel.iter_mut().filter(|v| v.is_finite()).enumerate() {
+            if let Some(r) = reference {
+                *v = unfold(*v, r);
+            }
+
+            if i == 0 {
+                ray_start = Some(*v);
+            }
+            reference = Some(*v);
+        }
+    }
+}
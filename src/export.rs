@@ -0,0 +1,171 @@
+use std::io::Write;
+
+/// Range in meters to the center of each of the first `gates` gates.
+///
+/// `meters_to_first_cell` is taken as the start of the first gate, the end of the range hidden
+/// by the transmit pulse.
+pub fn gate_ranges(param: &silv::ParamDescription, gates: usize) -> Vec<f32> {
+    (0..gates)
+        .map(|i| param.meters_to_first_cell + (i as f32 + 0.5) * param.meters_between_cells)
+        .collect()
+}
+
+/// Drops every gate whose center is further than `max_range` meters, such as gates past the
+/// unambiguous range that hold folded echoes. Each moment is cut at its own gate spacing.
+pub fn trim_range(radar: &mut silv::RadarFile, max_range: f32) {
+    let rays = radar.sweeps.iter().flat_map(|s| &s.rays);
+    let longest = rays
+        .flat_map(|r| r.data.values().map(Vec::len))
+        .max()
+        .unwrap_or(0);
+    let kept: std::collections::HashMap<_, _> = radar
+        .params
+        .iter()
+        .map(|(name, param)| {
+            let ranges = gate_ranges(param, longest);
+            let kept = ranges
+                .iter()
+                .take_while(|&&range| range <= max_range)
+                .count();
+            (name.clone(), kept)
+        })
+        .collect();
+
+    for ray in radar.sweeps.iter_mut().flat_map(|s| &mut s.rays) {
+        for (name, values) in &mut ray.data {
+            if let Some(&kept) = kept.get(name) {
+                values.truncate(kept);
+            }
+        }
+    }
+}
+
+/// Writes one row per gate with the azimuth, elevation, range to the gate center and every
+/// moment.
+///
+/// With `locations`, the antenna altitude in meters above sea level such as the header's
+/// [`crate::Site::altitude`], every row also has the gate latitude, longitude and height from
+/// [`crate::gate_location`], which assumes PPI sweeps with meteorological azimuths. Moment
+/// columns are sorted by name. Missing (NaN) values are written as `fill_value`, or left empty
+/// when it is `None`.
+pub fn write_csv(
+    radar: &silv::RadarFile,
+    writer: impl Write,
+    locations: Option<f32>,
+    fill_value: Option<f64>,
+) -> std::io::Result<()> {
+    let mut writer = std::io::BufWriter::new(writer);
+
+    let mut moments: Vec<_> = radar.params.keys().collect();
+    moments.sort();
+
+    write!(writer, "azimuth,elevation,range")?;
+    if locations.is_some() {
+        write!(writer, ",latitude,longitude,height")?;
+    }
+    for name in &moments {
+        write!(writer, ",{name}")?;
+    }
+    writeln!(writer)?;
+
+    for sweep in &radar.sweeps {
+        for ray in &sweep.rays {
+            let gates = ray.data.values().map(Vec::len).max().unwrap_or(0);
+            let ranges = match moments.first() {
+                Some(name) => gate_ranges(&radar.params[name.as_str()], gates),
+                None => Vec::new(),
+            };
+            for (gate, range) in ranges.into_iter().enumerate() {
+                write!(writer, "{},{},{range}", ray.azimuth, sweep.elevation)?;
+                if let Some(altitude) = locations {
+                    let (lat, lon, height) = crate::gate_location(
+                        sweep.latitude,
+                        sweep.longitude,
+                        altitude,
+                        sweep.elevation,
+                        ray.azimuth,
+                        range,
+                    );
+                    write!(writer, ",{lat},{lon},{height}")?;
+                }
+
+                for name in &moments {
+                    match ray.data.get(name.as_str()).and_then(|d| d.get(gate)) {
+                        Some(v) if !v.is_nan() => write!(writer, ",{v}")?,
+                        _ => match fill_value {
+                            Some(fill) => write!(writer, ",{fill}")?,
+                            None => write!(writer, ",")?,
+                        },
+                    }
+                }
+                writeln!(writer)?;
+            }
+        }
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SyntheticScan;
+
+    fn scan() -> SyntheticScan {
+        SyntheticScan {
+            rays: 2,
+            gates: 10,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn gate_ranges_are_gate_centers() {
+        let scan = SyntheticScan {
+            tx_pulse_blind_len: 100,
+            ..scan()
+        };
+        let (_, radar) = crate::read_rhix(&scan.to_bytes()).unwrap();
+
+        let ranges = gate_ranges(&radar.params["REF"], 10);
+        assert_eq!(ranges.len(), 10);
+        assert_eq!(ranges[0], 125.0);
+        assert_eq!(ranges[9], 575.0);
+    }
+
+    #[test]
+    fn missing_gates_are_written_as_the_fill_value() {
+        let scan = SyntheticScan {
+            moments: vec![crate::Moment::Reflectivity],
+            ..scan()
+        };
+        let bytes = scan.to_bytes_with(|_, _, gate| match gate {
+            0 => 0,
+            _ => 32768,
+        });
+        let (_, radar) = crate::read_rhix(&bytes).unwrap();
+
+        let rows = |fill_value| {
+            let mut csv = Vec::new();
+            write_csv(&radar, &mut csv, None, fill_value).unwrap();
+            String::from_utf8(csv).unwrap()
+        };
+        let filled = rows(Some(-9999.0));
+        let mut lines = filled.lines();
+        assert_eq!(lines.next(), Some("azimuth,elevation,range,REF"));
+        assert_eq!(lines.next(), Some("0,0.5,25,-9999"));
+        assert_eq!(lines.next(), Some("0,0.5,75,0"));
+        assert_eq!(rows(None).lines().nth(1), Some("0,0.5,25,"));
+    }
+
+    #[test]
+    fn trim_range_drops_far_gates() {
+        let (_, mut radar) = crate::read_rhix(&scan().to_bytes()).unwrap();
+        // Gate centers are 25 m, 75 m and so on, so five are within 250 m
+        trim_range(&mut radar, 250.0);
+        for ray in &radar.sweeps[0].rays {
+            assert_eq!(ray.data.len(), 4);
+            assert!(ray.data.values().all(|values| values.len() == 5));
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use crate::header::offset;
+use crate::{Endian, Moment, RhixTime, HEADER_SIZE};
+
+/// A synthetic single sweep PPI scan, for building `.rhix` buffers in tests and benchmarks.
+///
+/// The header describes a radar at 35° N, 135° E recording from 2023-05-01 12:00 to 12:01 UTC,
+/// turning at 18° per second with a PRF of 1000 Hz and a 16 m/s Nyquist velocity. The
+/// polarization is dual when a dual-pol moment is recorded and single otherwise. Fields without
+/// a unit are raw header words, written as they are; every header word not listed here is zero.
+#[derive(Debug, Clone)]
+pub struct SyntheticScan {
+    pub rays: u16,
+    pub gates: u16,
+    /// Meters between gates.
+    pub gate_res: u16,
+    /// Degrees, the same for every ray.
+    pub elevation: f32,
+    /// Recorded moments, stored in record_item bit order whatever order they are listed in.
+    pub moments: Vec<Moment>,
+    pub endian: Endian,
+    pub version: u16,
+    pub start_time: RhixTime,
+    /// Spread evenly over the rays from `start_time`.
+    pub end_time: RhixTime,
+    /// Minutes east of UTC that the times are recorded in.
+    pub timezone: i16,
+    pub product_number: u16,
+    pub model_type: u16,
+    /// Hundredths of a degree.
+    pub azimuth_offset: u16,
+    pub radar_const_h: i16,
+    pub radar_const_v: i16,
+    pub noise_power_h_short: i16,
+    pub thresh_power_short: i16,
+    /// Meters.
+    pub tx_pulse_blind_len: u16,
+    pub observation_mode: u16,
+    /// Tenths of a revolution per minute.
+    pub rotation_speed: u16,
+    pub signal_flag: u16,
+}
+
+impl Default for SyntheticScan {
+    fn default() -> Self {
+        Self {
+            rays: 360,
+            gates: 1000,
+            gate_res: 50,
+            elevation: 0.5,
+            moments: vec![
+                Moment::Reflectivity,
+                Moment::Velocity,
+                Moment::Zdr,
+                Moment::Rho,
+            ],
+            endian: Endian::Little,
+            version: 3,
+            start_time: RhixTime {
+                year: 2023,
+                month: 5,
+                day: 1,
+                hour: 12,
+                ..Default::default()
+            },
+            end_time: RhixTime {
+                year: 2023,
+                month: 5,
+                day: 1,
+                hour: 12,
+                minute: 1,
+                ..Default::default()
+            },
+            timezone: 0,
+            product_number: 0,
+            model_type: 0,
+            azimuth_offset: 0,
+            radar_const_h: 0,
+            radar_const_v: 0,
+            noise_power_h_short: 0,
+            thresh_power_short: 0,
+            tx_pulse_blind_len: 0,
+            observation_mode: 1,
+            rotation_speed: 30,
+            signal_flag: 0,
+        }
+    }
+}
+
+impl SyntheticScan {
+    /// The whole file, with rays evenly spaced in azimuth and raw gate values cycling through
+    /// `32768..36768`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_bytes_with(|_, _, gate| 32768 + gate % 4000)
+    }
+
+    /// The whole file, taking the raw value of every gate from `value(ray, moment, gate)`.
+    ///
+    /// # Panics
+    ///
+    /// When a ray's moments are too large for the observed block size to hold, more than
+    /// 65535 bytes.
+    pub fn to_bytes_with(&self, mut value: impl FnMut(u16, Moment, u16) -> u16) -> Vec<u8> {
+        let moments = self.stored_moments();
+        let record_item = moments.iter().fold(0, |bits, m| bits | 1 << m.bit());
+        let block = 2 + moments.len() as u32 * self.gates as u32 * 2;
+        let block = u16::try_from(block).unwrap_or_else(|_| {
+            panic!(
+                "{} moments of {} gates need a {block} byte block, more than a u16 holds",
+                moments.len(),
+                self.gates
+            )
+        });
+
+        let polarization = moments.iter().any(|m| Moment::DUAL_POL.contains(m)) as u16;
+
+        let mut bytes = vec![0; HEADER_SIZE as usize];
+        for (at, value) in [
+            (offset::HEADER_SIZE, HEADER_SIZE),
+            (offset::VERSION, self.version),
+            (offset::TIMEZONE, self.timezone as u16),
+            (offset::PRODUCT_NUMBER, self.product_number),
+            (offset::MODEL_TYPE, self.model_type),
+            (offset::AZIMUTH_OFFSET, self.azimuth_offset),
+            (offset::POLARIZATION, polarization),
+            (offset::RADAR_CONST_H, self.radar_const_h as u16),
+            (offset::RADAR_CONST_V, self.radar_const_v as u16),
+            (offset::NOISE_POWER_H_SHORT, self.noise_power_h_short as u16),
+            (offset::THRESH_POWER_SHORT, self.thresh_power_short as u16),
+            (offset::PRF1, 1000),
+            (offset::NYQUIST, 160),
+            (offset::SAMPLE_NUM, 32),
+            (offset::TX_PULSE_BLIND_LEN, self.tx_pulse_blind_len),
+            (offset::OBSERVATION_MODE, self.observation_mode),
+            (offset::ROTATION_SPEED, self.rotation_speed),
+            (offset::RAYS, self.rays),
+            (offset::GATES, self.gates),
+            (offset::GATE_RES, self.gate_res),
+            (offset::SCAN_NUM, 1),
+            (offset::TOTAL_SCANS, 1),
+            (offset::RECORD_ITEM, record_item),
+            (offset::SIGNAL_FLAG, self.signal_flag),
+        ] {
+            self.patch(&mut bytes, at, value);
+        }
+        for (at, time) in [
+            (offset::START_TIME, self.start_time),
+            (offset::END_TIME, self.end_time),
+        ] {
+            self.patch(&mut bytes, at, time.year);
+            bytes[at + 2..at + 8].copy_from_slice(&[
+                time.month,
+                time.day,
+                time.hour,
+                time.minute,
+                time.second,
+                time.padding,
+            ]);
+        }
+        for (at, value) in [
+            (offset::LATITUDE, 3_500_000i32 as u32),
+            (offset::LONGITUDE, 13_500_000),
+            (offset::ALTITUDE, 100),
+            (offset::TX_FREQUENCY, 9_400_000),
+        ] {
+            let value = match self.endian {
+                Endian::Little => value.to_le_bytes(),
+                Endian::Big => value.to_be_bytes(),
+            };
+            bytes[at..at + 4].copy_from_slice(&value);
+        }
+        debug_assert_eq!(offset::RESERVED + 8, HEADER_SIZE as usize);
+
+        let endian = self.endian;
+        let put = |bytes: &mut Vec<u8>, values: &[u16]| {
+            for v in values {
+                match endian {
+                    Endian::Little => bytes.extend(v.to_le_bytes()),
+                    Endian::Big => bytes.extend(v.to_be_bytes()),
+                }
+            }
+        };
+
+        let elevation = (self.elevation * 100.0).round() as u16;
+        let step = 36000 / self.rays.max(1) as u32;
+        for ray in 0..self.rays {
+            let azimuth = (ray as u32 * step) as u16;
+            put(&mut bytes, &[6, azimuth, elevation, block]);
+            for &moment in &moments {
+                for gate in 0..self.gates {
+                    put(&mut bytes, &[value(ray, moment, gate)]);
+                }
+            }
+        }
+
+        bytes
+    }
+
+    /// Where ray `ray` starts in [`SyntheticScan::to_bytes`], at the size of its angle block.
+    pub fn ray_offset(&self, ray: u16) -> usize {
+        let ray_bytes = 8 + self.stored_moments().len() * self.gates as usize * 2;
+        HEADER_SIZE as usize + ray as usize * ray_bytes
+    }
+
+    /// Overwrites the number at byte `offset` of a buffer from [`SyntheticScan::to_bytes`] with
+    /// `value`, in the scan's byte order. For changing header fields and angles in tests.
+    pub fn patch(&self, bytes: &mut [u8], offset: usize, value: u16) {
+        let value = match self.endian {
+            Endian::Little => value.to_le_bytes(),
+            Endian::Big => value.to_be_bytes(),
+        };
+        bytes[offset..offset + 2].copy_from_slice(&value);
+    }
+
+    /// The moments in the order they are stored, without repeats.
+    fn stored_moments(&self) -> Vec<Moment> {
+        let mut moments = self.moments.clone();
+        moments.sort_by_key(|m| m.bit());
+        moments.dedup();
+        moments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Polarization, RhixHeader};
+
+    #[test]
+    fn header_reads_back() {
+        for endian in [Endian::Little, Endian::Big] {
+            let scan = SyntheticScan {
+                rays: 4,
+                gates: 10,
+                endian,
+                ..Default::default()
+            };
+            let header = RhixHeader::read(&scan.to_bytes()[..], endian).unwrap();
+            assert_eq!(header.version, 3);
+            assert_eq!((header.geometry.rays, header.geometry.gates), (4, 10));
+            assert_eq!(header.geometry.gate_res, 50);
+            assert_eq!(header.stored_moments(), scan.moments);
+        }
+    }
+
+    #[test]
+    fn polarization_follows_the_moments() {
+        let read = |moments| {
+            let scan = SyntheticScan {
+                rays: 1,
+                gates: 1,
+                moments,
+                ..Default::default()
+            };
+            RhixHeader::read(&scan.to_bytes()[..], Endian::Little).unwrap()
+        };
+        let dual = read(SyntheticScan::default().moments);
+        assert_eq!(dual.calibration.polarization, Polarization::Dual);
+        let single = read(vec![Moment::Reflectivity, Moment::Velocity]);
+        assert_eq!(single.calibration.polarization, Polarization::Single);
+    }
+
+    #[test]
+    fn rays_follow_the_header() {
+        let scan = SyntheticScan {
+            rays: 4,
+            gates: 10,
+            moments: vec![Moment::Velocity, Moment::Reflectivity, Moment::Velocity],
+            ..Default::default()
+        };
+        let bytes = scan.to_bytes_with(|ray, _, gate| ray * 100 + gate);
+        assert_eq!(bytes.len(), scan.ray_offset(4));
+
+        let ray = scan.ray_offset(1);
+        let number = |i: usize| u16::from_le_bytes([bytes[ray + 2 * i], bytes[ray + 2 * i + 1]]);
+        // Angle block size, azimuth, elevation, then two moments of 10 gates
+        assert_eq!(
+            [number(0), number(1), number(2), number(3)],
+            [6, 9000, 50, 42]
+        );
+        assert_eq!([number(4), number(13), number(14)], [100, 109, 100]);
+    }
+
+    #[test]
+    fn patch_keeps_byte_order() {
+        let scan = SyntheticScan {
+            rays: 1,
+            gates: 1,
+            endian: Endian::Big,
+            ..Default::default()
+        };
+        let mut bytes = scan.to_bytes();
+        scan.patch(&mut bytes, offset::VERSION, 0x0102);
+        assert_eq!(bytes[offset::VERSION..offset::VERSION + 2], [1, 2]);
+    }
+
+    #[test]
+    fn large_block_is_not_wrapped() {
+        // 4 moments of 8191 gates hold 65530 bytes, just inside a u16 with its size field
+        let scan = SyntheticScan {
+            rays: 1,
+            gates: 8191,
+            ..Default::default()
+        };
+        let bytes = scan.to_bytes();
+        let block = scan.ray_offset(0) + 6;
+        assert_eq!(u16::from_le_bytes([bytes[block], bytes[block + 1]]), 65530);
+    }
+
+    #[test]
+    #[should_panic(expected = "more than a u16 holds")]
+    fn oversized_block_panics() {
+        let scan = SyntheticScan {
+            rays: 1,
+            gates: 20000,
+            ..Default::default()
+        };
+        scan.to_bytes();
+    }
+}
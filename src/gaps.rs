@@ -0,0 +1,55 @@
+/// Linearly interpolates across runs of at most `max_gap` missing (NaN) gates in every moment of
+/// every ray. Runs touching either end of a ray, and longer runs, are left missing.
+pub fn fill_gaps(sweep: &mut silv::Sweep, max_gap: usize) {
+    if max_gap == 0 {
+        return;
+    }
+
+    for values in sweep.rays.iter_mut().flat_map(|r| r.data.values_mut()) {
+        let mut last_valid: Option<usize> = None;
+        for i in 0..values.len() {
+            if values[i].is_nan() {
+                continue;
+            }
+
+            if let Some(start) = last_valid {
+                let gap = i - start - 1;
+                if gap > 0 && gap <= max_gap {
+                    let (from, to) = (values[start], values[i]);
+                    for j in 1..=gap {
+                        let t = j as f64 / (gap + 1) as f64;
+                        values[start + j] = from + (to - from) * t;
+                    }
+                }
+            }
+            last_valid = Some(i);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_one_gate_gaps_only() {
+        let scan = crate::SyntheticScan {
+            rays: 1,
+            gates: 10,
+            moments: vec![crate::Moment::Reflectivity],
+            ..Default::default()
+        };
+        // A one gate gap at 2 and a two gate gap at 5 and 6, between values rising by 1 dBZ
+        let bytes = scan.to_bytes_with(|_, _, gate| match gate {
+            2 | 5 | 6 => 0,
+            _ => 32768 + gate * 100,
+        });
+        let (_, mut radar) = crate::read_rhix(&bytes).unwrap();
+        fill_gaps(&mut radar.sweeps[0], 1);
+
+        let values = &radar.sweeps[0].rays[0].data["REF"];
+        assert!((values[2] - 2.0).abs() < 1e-9);
+        assert!(values[5].is_nan() && values[6].is_nan());
+        assert!((values[7] - 7.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,79 @@
+/// Mean earth radius in meters. The earth is treated as a sphere, which puts gates within a few
+/// meters of their WGS84 position at radar ranges.
+const EARTH_RADIUS: f64 = 6_371_000.0;
+/// Effective earth radius factor of the standard 4/3 earth model of beam refraction.
+const EFFECTIVE_RADIUS_FACTOR: f64 = 4.0 / 3.0;
+
+/// Latitude and longitude in degrees and height above sea level in meters of the gate `range`
+/// meters along a beam at `elevation` and `azimuth` degrees from a site at `latitude`,
+/// `longitude` and `altitude`.
+///
+/// The beam height and ground distance follow the 4/3 earth model of Doviak and Zrnić, and the
+/// gate is placed along the great circle leaving the site at `azimuth`.
+pub fn gate_location(
+    latitude: f32,
+    longitude: f32,
+    altitude: f32,
+    elevation: f32,
+    azimuth: f32,
+    range: f32,
+) -> (f64, f64, f64) {
+    let effective_radius = EFFECTIVE_RADIUS_FACTOR * EARTH_RADIUS;
+    let range = range as f64;
+    let elevation = (elevation as f64).to_radians();
+
+    let height = (range.powi(2)
+        + effective_radius.powi(2)
+        + 2.0 * range * effective_radius * elevation.sin())
+    .sqrt()
+        - effective_radius;
+    let distance =
+        effective_radius * (range * elevation.cos() / (effective_radius + height)).asin();
+
+    let angle = distance / EARTH_RADIUS;
+    let (lat, lon) = (
+        (latitude as f64).to_radians(),
+        (longitude as f64).to_radians(),
+    );
+    let azimuth = (azimuth as f64).to_radians();
+    let gate_lat = (lat.sin() * angle.cos() + lat.cos() * angle.sin() * azimuth.cos()).asin();
+    let gate_lon = lon
+        + (azimuth.sin() * angle.sin() * lat.cos()).atan2(angle.cos() - lat.sin() * gate_lat.sin());
+
+    (
+        gate_lat.to_degrees(),
+        gate_lon.to_degrees(),
+        altitude as f64 + height,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gate_due_north_of_the_site() {
+        let (header, _) = crate::read_rhix(&crate::SyntheticScan::default().to_bytes()).unwrap();
+        let site = &header.site;
+        let (lat, lon, height) = gate_location(
+            site.latitude,
+            site.longitude,
+            site.altitude as f32,
+            0.5,
+            0.0,
+            50_000.0,
+        );
+        // 50 km at 0.5 degrees rises r sin(e) + r^2 / (2 * 4/3 R), 436 m + 147 m, and covers
+        // 49995 m of ground, 0.4496 degrees of arc
+        assert!((lat - 35.4496).abs() < 1e-4, "{lat}");
+        assert!((lon - 135.0).abs() < 1e-9, "{lon}");
+        assert!((height - 683.46).abs() < 0.01, "{height}");
+    }
+
+    #[test]
+    fn gate_due_east_along_the_equator() {
+        let (lat, lon, _) = gate_location(0.0, 0.0, 0.0, 0.5, 90.0, 50_000.0);
+        assert!(lat.abs() < 1e-9, "{lat}");
+        assert!((lon - 0.4496).abs() < 1e-4, "{lon}");
+    }
+}
@@ -0,0 +1,627 @@
+use crate::{ConvertError, Moment};
+use chrono::TimeZone;
+use serde::Serialize;
+use std::io::Read;
+
+/// Size in bytes of the fixed header at the start of every file.
+pub const HEADER_SIZE: u16 = 156;
+
+/// Byte offsets of the header fields, in the order [`RhixHeader::read`] reads them. Used by
+/// [`crate::SyntheticScan`] to lay out headers, and by tests to patch single fields.
+pub(crate) mod offset {
+    pub const HEADER_SIZE: usize = 0;
+    pub const VERSION: usize = 2;
+    /// Eight bytes: a u16 year, then month, day, hour, minute, second and a padding byte.
+    pub const START_TIME: usize = 4;
+    pub const END_TIME: usize = 12;
+    pub const TIMEZONE: usize = 20;
+    pub const PRODUCT_NUMBER: usize = 22;
+    pub const MODEL_TYPE: usize = 24;
+    pub const LATITUDE: usize = 26;
+    pub const LONGITUDE: usize = 30;
+    pub const ALTITUDE: usize = 34;
+    pub const AZIMUTH_OFFSET: usize = 38;
+    pub const TX_FREQUENCY: usize = 40;
+    pub const POLARIZATION: usize = 44;
+    pub const RADAR_CONST_H: usize = 58;
+    pub const RADAR_CONST_V: usize = 60;
+    pub const NOISE_POWER_H_SHORT: usize = 62;
+    pub const THRESH_POWER_SHORT: usize = 66;
+    pub const PRF1: usize = 74;
+    pub const NYQUIST: usize = 80;
+    pub const SAMPLE_NUM: usize = 82;
+    pub const TX_PULSE_BLIND_LEN: usize = 84;
+    pub const OBSERVATION_MODE: usize = 96;
+    pub const ROTATION_SPEED: usize = 98;
+    pub const RAYS: usize = 100;
+    pub const GATES: usize = 102;
+    pub const GATE_RES: usize = 104;
+    pub const SCAN_NUM: usize = 106;
+    pub const TOTAL_SCANS: usize = 108;
+    pub const RECORD_ITEM: usize = 136;
+    pub const SIGNAL_FLAG: usize = 138;
+    /// The eight reserved bytes that end the header.
+    pub const RESERVED: usize = 148;
+}
+
+/// Format versions whose field layout and scalings this parser is written for. The layout
+/// follows the `.rhix` description in the WR2120 manual
+/// (<https://www.manualslib.com/manual/1935797/Furuno-Wr2120.html?page=72>); the version number
+/// 3 is not yet confirmed against that page or a real file.
+pub const KNOWN_VERSIONS: &[u16] = &[3];
+
+/// Byte order of the numbers in a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+impl std::str::FromStr for Endian {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "le" => Ok(Endian::Little),
+            "be" => Ok(Endian::Big),
+            _ => Err(format!("Unknown endianness [{s}], expected le or be")),
+        }
+    }
+}
+
+/// Speed of light in m/s, for ranges derived from pulse timing.
+const SPEED_OF_LIGHT: f32 = 299_792_458.0;
+
+/// Largest timezone offset in use anywhere, UTC+14:00.
+const MAX_TIMEZONE_MINUTES: i16 = 14 * 60;
+
+/// Scan geometry recorded in the header's observation mode word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ObservationMode {
+    /// Azimuth scan at a fixed elevation (codes 1, 3 and 4).
+    Ppi,
+    /// Elevation scan at a fixed azimuth (code 2).
+    Rhi,
+    /// Any other code, parsed like an RHI.
+    Unknown(u16),
+}
+
+impl ObservationMode {
+    pub fn from_code(code: u16) -> Self {
+        match code {
+            1 | 3 | 4 => ObservationMode::Ppi,
+            2 => ObservationMode::Rhi,
+            c => ObservationMode::Unknown(c),
+        }
+    }
+}
+
+/// Polarization recorded in the header's polarization word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Polarization {
+    /// Horizontal only (code 0), so no dual-pol moments can be measured.
+    Single,
+    /// Simultaneous horizontal and vertical (code 1).
+    Dual,
+    /// Any other code.
+    Unknown(u16),
+}
+
+impl Polarization {
+    pub fn from_code(code: u16) -> Self {
+        match code {
+            0 => Polarization::Single,
+            1 => Polarization::Dual,
+            c => Polarization::Unknown(c),
+        }
+    }
+}
+
+/// Date and time as stored in the header, without any timezone applied.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct RhixTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// Reserved byte after the seconds, zero in every known file.
+    pub padding: u8,
+}
+
+impl RhixTime {
+    /// Converts the time recorded at `offset` to UTC.
+    pub fn to_utc(
+        &self,
+        offset: chrono::FixedOffset,
+    ) -> Result<chrono::DateTime<chrono::Utc>, ConvertError> {
+        // Built from naive parts so that no local time lookup can be ambiguous
+        chrono::NaiveDate::from_ymd_opt(self.year as i32, self.month as u32, self.day as u32)
+            .and_then(|date| {
+                date.and_hms_opt(self.hour as u32, self.minute as u32, self.second as u32)
+            })
+            .and_then(|local| {
+                local.checked_sub_signed(chrono::Duration::seconds(offset.local_minus_utc() as i64))
+            })
+            .map(|utc| chrono::Utc.from_utc_datetime(&utc))
+            .ok_or(ConvertError::InvalidTime(*self))
+    }
+
+    /// Whether every part is zero, which marks a time that was never set.
+    pub fn is_unset(&self) -> bool {
+        *self == RhixTime::default()
+    }
+
+    /// Reads the seven time bytes and the padding byte that follows them. Times are only
+    /// recorded to the second, ray times within a scan are interpolated from them.
+    fn read(mut data: impl Read, endian: Endian) -> Result<Self, ConvertError> {
+        Ok(RhixTime {
+            year: read_num!(data, u16, endian),
+            month: read_num!(data, u8, endian),
+            day: read_num!(data, u8, endian),
+            hour: read_num!(data, u8, endian),
+            minute: read_num!(data, u8, endian),
+            second: read_num!(data, u8, endian),
+            padding: read_num!(data, u8, endian),
+        })
+    }
+}
+
+/// Which moments a file records, decoded from the record_item bitmask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct RecordItem {
+    pub rain_rate: bool,
+    pub reflectivity: bool,
+    pub velocity: bool,
+    pub zdr: bool,
+    pub kdp: bool,
+    pub phi: bool,
+    pub rho: bool,
+    pub spectrum_width: bool,
+    pub quality: bool,
+}
+
+impl RecordItem {
+    pub fn from_bits(bits: u16) -> Self {
+        let bit = |i: u16| bits >> i & 1 != 0;
+        RecordItem {
+            rain_rate: bit(0),
+            reflectivity: bit(1),
+            velocity: bit(2),
+            zdr: bit(3),
+            kdp: bit(4),
+            phi: bit(5),
+            rho: bit(6),
+            spectrum_width: bit(7),
+            quality: bit(8),
+        }
+    }
+
+    /// The flags in [`Moment::ALL`] order.
+    pub fn to_array(self) -> [bool; 9] {
+        [
+            self.rain_rate,
+            self.reflectivity,
+            self.velocity,
+            self.zdr,
+            self.kdp,
+            self.phi,
+            self.rho,
+            self.spectrum_width,
+            self.quality,
+        ]
+    }
+}
+
+/// Signal processing applied before recording, decoded from the signal_flag word.
+///
+/// The bit meanings are assumptions, not confirmed by the format documentation: bit 0 is taken
+/// to be set when the clutter filter ran and bit 1 when rain attenuation was corrected. Any
+/// other set bits are kept in `unknown`. No bit is known to mark moments as invalid, so no data
+/// is masked by them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SignalFlags {
+    pub clutter_filter: bool,
+    pub attenuation_correction: bool,
+    /// The remaining bits, unchanged.
+    pub unknown: u16,
+}
+
+impl SignalFlags {
+    pub fn from_bits(bits: u16) -> Self {
+        SignalFlags {
+            clutter_filter: bits & 1 != 0,
+            attenuation_correction: bits & 2 != 0,
+            unknown: bits & !0b11,
+        }
+    }
+}
+
+/// Every field of the 156 byte header, grouped by what it describes, followed by values derived
+/// from them. The groups and the fields within them are in file order, and `--dump-header`
+/// writes them nested the same way.
+///
+/// Fields with a known scaling are decoded into physical units, the rest are kept raw.
+#[derive(Debug, Clone, Serialize)]
+pub struct RhixHeader {
+    pub version: u16,
+    pub timing: Timing,
+    pub site: Site,
+    pub calibration: RadarCalibration,
+    pub pulse: Pulse,
+    pub geometry: ScanGeometry,
+    pub correction: Correction,
+    pub record_item: RecordItem,
+    pub signal_flag: SignalFlags,
+    /// When the clutter reference map in use was recorded, in local time. All zero when there
+    /// is none, see [`RhixTime::is_unset`].
+    pub clutter_ref_file: RhixTime,
+    /// The reserved last 8 bytes of the header, zero in every known file.
+    pub reserved: u64,
+}
+
+/// When the scan was recorded.
+#[derive(Debug, Clone, Serialize)]
+pub struct Timing {
+    pub start_time: RhixTime,
+    pub end_time: RhixTime,
+    /// Offset of the recorded times from UTC in minutes.
+    pub timezone: i16,
+}
+
+/// Which radar recorded the scan and where it stands.
+#[derive(Debug, Clone, Serialize)]
+pub struct Site {
+    pub product_number: u16,
+    pub model_type: u16,
+    /// Degrees north.
+    pub latitude: f32,
+    /// Degrees east.
+    pub longitude: f32,
+    /// Antenna height above sea level in meters.
+    pub altitude: i32,
+    /// Antenna mounting offset from true north in hundredths of a degree, the same units as
+    /// the ray azimuths.
+    pub azimuth_offset: u16,
+}
+
+/// Antenna, transmitter and receiver characteristics.
+#[derive(Debug, Clone, Serialize)]
+pub struct RadarCalibration {
+    pub tx_frequency: u32,
+    pub polarization: Polarization,
+    pub gain_h: u16,
+    pub gain_v: u16,
+    /// Half power beamwidths in hundredths of a degree, see `beamwidth_h` and `beamwidth_v`.
+    pub half_width_h: u16,
+    pub half_width_v: u16,
+    pub tx_power_h: u16,
+    pub tx_power_v: u16,
+    /// Radar constants of the horizontal and vertical channels in hundredths of a dB, relating
+    /// received power and range to reflectivity.
+    pub radar_const_h: i16,
+    pub radar_const_v: i16,
+    pub noise_power_h_short: i16,
+    pub noise_power_h_long: i16,
+    pub thresh_power_short: i16,
+    pub thresh_power_long: i16,
+    /// Horizontal and vertical beamwidths in degrees from `half_width_h` and `half_width_v`.
+    /// Not stored in the file.
+    pub beamwidth_h: f32,
+    pub beamwidth_v: f32,
+}
+
+/// Transmitted pulses and how they were sampled.
+#[derive(Debug, Clone, Serialize)]
+pub struct Pulse {
+    /// Transmit pulse specification code, raw. Selects the pulse widths and modulations below.
+    pub tx_pulse_spec: u16,
+    pub prf_mode: u16,
+    pub prf1: u16,
+    pub prf2: u16,
+    pub prf3: u16,
+    /// Meters per second.
+    pub nyquist: f32,
+    /// Pulses averaged into every ray. More samples lower the variance of the moment estimates,
+    /// so noisier velocity and spectrum width are expected from scans with few of them.
+    pub sample_num: u16,
+    /// Range hidden by the transmit pulse, recorded in meters. The first gate starts past it.
+    pub tx_pulse_blind_len: u16,
+    /// Width and modulation bandwidth of the short and long pulses, raw. A longer or
+    /// compressed pulse raises sensitivity at the cost of the range it hides near the radar.
+    pub short_pulse_width: u16,
+    pub short_pulse_mod_bandwidth: u16,
+    pub long_pulse_width: u16,
+    pub long_pulse_mod_bandwidth: u16,
+    /// Where the short pulse hands over to the long pulse, raw. Zero for a single pulse scan.
+    /// Gates on both sides are still spaced by `gate_res`, since each pulse's own resolution is
+    /// not recorded.
+    pub pulse_switchpoint: u16,
+    /// Maximum unambiguous range in meters for the effective PRF, see
+    /// [`Pulse::unambiguous_range`]. Not stored in the file.
+    pub max_range: Option<f32>,
+}
+
+/// How the antenna moved and how each ray is divided into gates.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanGeometry {
+    pub observation_mode: ObservationMode,
+    /// Degrees per second.
+    pub rotation_speed: f32,
+    pub rays: u16,
+    pub gates: u16,
+    /// Meters between gates.
+    pub gate_res: u16,
+    pub scan_num: u16,
+    pub total_scans: u16,
+}
+
+/// Rain rate estimation and correction coefficients, raw.
+#[derive(Debug, Clone, Serialize)]
+pub struct Correction {
+    pub rain_intensity_est: u16,
+    pub zr_coeff_b: u16,
+    pub zr_coeff_beta: u16,
+    pub kdp_coeff_a: u16,
+    pub kdp_coeff_b: u16,
+    pub kdp_coeff_c: u16,
+    pub zh_corr: u16,
+    pub zh_corr_b1: u16,
+    pub zh_corr_b2: u16,
+    pub zh_corr_d1: u16,
+    pub zh_corr_d2: u16,
+    pub air_attenuation: u16,
+    pub rain_thresh: u16,
+}
+
+impl RhixHeader {
+    /// Reads the header from the start of an uncompressed file.
+    pub fn read(mut data: impl Read, endian: Endian) -> Result<Self, ConvertError> {
+        // Read whole first, so a short file fails with its length rather than an EOF error
+        let mut bytes = [0; HEADER_SIZE as usize];
+        let filled = crate::fill(&mut data, &mut bytes)?;
+        if filled < bytes.len() {
+            return Err(ConvertError::TruncatedHeader(filled));
+        }
+        let mut data = &bytes[..];
+
+        let header_size = read_num!(data, u16, endian);
+        if header_size != HEADER_SIZE {
+            return Err(ConvertError::HeaderSize(header_size));
+        }
+
+        let version = read_num!(data, u16, endian);
+        let timing = Timing {
+            start_time: RhixTime::read(&mut data, endian)?,
+            end_time: RhixTime::read(&mut data, endian)?,
+            timezone: read_num!(data, i16, endian),
+        };
+        let site = Site {
+            product_number: read_num!(data, u16, endian),
+            model_type: read_num!(data, u16, endian),
+            latitude: read_num!(data, i32, endian) as f32 / 100000.0,
+            longitude: read_num!(data, i32, endian) as f32 / 100000.0,
+            altitude: read_num!(data, i32, endian),
+            azimuth_offset: read_num!(data, u16, endian),
+        };
+        let mut calibration = RadarCalibration {
+            tx_frequency: read_num!(data, u32, endian),
+            polarization: Polarization::from_code(read_num!(data, u16, endian)),
+            gain_h: read_num!(data, u16, endian),
+            gain_v: read_num!(data, u16, endian),
+            half_width_h: read_num!(data, u16, endian),
+            half_width_v: read_num!(data, u16, endian),
+            tx_power_h: read_num!(data, u16, endian),
+            tx_power_v: read_num!(data, u16, endian),
+            radar_const_h: read_num!(data, i16, endian),
+            radar_const_v: read_num!(data, i16, endian),
+            noise_power_h_short: read_num!(data, i16, endian),
+            noise_power_h_long: read_num!(data, i16, endian),
+            thresh_power_short: read_num!(data, i16, endian),
+            thresh_power_long: read_num!(data, i16, endian),
+            beamwidth_h: 0.0,
+            beamwidth_v: 0.0,
+        };
+        calibration.beamwidth_h = calibration.half_width_h as f32 / 100.0;
+        calibration.beamwidth_v = calibration.half_width_v as f32 / 100.0;
+        let mut pulse = Pulse {
+            tx_pulse_spec: read_num!(data, u16, endian),
+            prf_mode: read_num!(data, u16, endian),
+            prf1: read_num!(data, u16, endian),
+            prf2: read_num!(data, u16, endian),
+            prf3: read_num!(data, u16, endian),
+            nyquist: read_num!(data, u16, endian) as f32 / 10.0,
+            sample_num: read_num!(data, u16, endian),
+            tx_pulse_blind_len: read_num!(data, u16, endian),
+            short_pulse_width: read_num!(data, u16, endian),
+            short_pulse_mod_bandwidth: read_num!(data, u16, endian),
+            long_pulse_width: read_num!(data, u16, endian),
+            long_pulse_mod_bandwidth: read_num!(data, u16, endian),
+            pulse_switchpoint: read_num!(data, u16, endian),
+            max_range: None,
+        };
+        pulse.max_range = pulse.unambiguous_range();
+        let geometry = ScanGeometry {
+            observation_mode: ObservationMode::from_code(read_num!(data, u16, endian)),
+            rotation_speed: read_num!(data, u16, endian) as f32 / 10.0 / 60.0 * 360.0,
+            rays: read_num!(data, u16, endian),
+            gates: read_num!(data, u16, endian),
+            gate_res: read_num!(data, u16, endian),
+            scan_num: read_num!(data, u16, endian),
+            total_scans: read_num!(data, u16, endian),
+        };
+        let correction = Correction {
+            rain_intensity_est: read_num!(data, u16, endian),
+            zr_coeff_b: read_num!(data, u16, endian),
+            zr_coeff_beta: read_num!(data, u16, endian),
+            kdp_coeff_a: read_num!(data, u16, endian),
+            kdp_coeff_b: read_num!(data, u16, endian),
+            kdp_coeff_c: read_num!(data, u16, endian),
+            zh_corr: read_num!(data, u16, endian),
+            zh_corr_b1: read_num!(data, u16, endian),
+            zh_corr_b2: read_num!(data, u16, endian),
+            zh_corr_d1: read_num!(data, u16, endian),
+            zh_corr_d2: read_num!(data, u16, endian),
+            air_attenuation: read_num!(data, u16, endian),
+            rain_thresh: read_num!(data, u16, endian),
+        };
+
+        Ok(RhixHeader {
+            version,
+            timing,
+            site,
+            calibration,
+            pulse,
+            geometry,
+            correction,
+            record_item: RecordItem::from_bits(read_num!(data, u16, endian)),
+            signal_flag: SignalFlags::from_bits(read_num!(data, u16, endian)),
+            clutter_ref_file: RhixTime::read(&mut data, endian)?,
+            reserved: read_num!(data, u64, endian),
+        })
+    }
+
+    /// The timezone the header times are recorded in.
+    pub fn utc_offset(&self) -> Result<chrono::FixedOffset, ConvertError> {
+        let timezone = self.timing.timezone;
+        if timezone.unsigned_abs() > MAX_TIMEZONE_MINUTES as u16 {
+            return Err(ConvertError::InvalidTimezone(timezone));
+        }
+
+        chrono::FixedOffset::east_opt(timezone as i32 * 60)
+            .ok_or(ConvertError::InvalidTimezone(timezone))
+    }
+}
+
+impl Pulse {
+    /// Maximum unambiguous range in meters, `c / (2 * PRF)`.
+    ///
+    /// A `prf_mode` of 0 is taken as single PRF using `prf1`. Any other mode is taken as a
+    /// dual or triple PRF scheme, where the highest PRF in use limits the range. PRFs are
+    /// assumed to be recorded in Hz. `None` when no PRF is set.
+    pub fn unambiguous_range(&self) -> Option<f32> {
+        let prf = match self.prf_mode {
+            0 => self.prf1,
+            _ => self.prf1.max(self.prf2).max(self.prf3),
+        };
+
+        (prf != 0).then(|| SPEED_OF_LIGHT / (2.0 * prf as f32))
+    }
+}
+
+impl RhixHeader {
+    /// The recorded moments in the order their gates are stored within each ray.
+    ///
+    /// This order is assumed to be ascending record_item bit, the order the format
+    /// documentation lists the bits in. It has not been confirmed against the documentation's
+    /// ray layout or a real file with several moments.
+    pub fn stored_moments(&self) -> Vec<Moment> {
+        Moment::ALL
+            .into_iter()
+            .filter(|&m| self.records(m))
+            .collect()
+    }
+
+    /// Reserved fields holding something other than zero, by name. Known files have none, so
+    /// any are a sign of another format version or a misread header.
+    pub fn nonzero_reserved(&self) -> Vec<(&'static str, u64)> {
+        [
+            ("start_time.padding", self.timing.start_time.padding as u64),
+            ("end_time.padding", self.timing.end_time.padding as u64),
+            (
+                "clutter_ref_file.padding",
+                self.clutter_ref_file.padding as u64,
+            ),
+            ("reserved", self.reserved),
+        ]
+        .into_iter()
+        .filter(|&(_, value)| value != 0)
+        .collect()
+    }
+
+    /// Whether the file records `moment`.
+    pub fn records(&self, moment: Moment) -> bool {
+        self.record_item.to_array()[moment.bit()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SyntheticScan;
+
+    #[test]
+    fn month_13_is_an_invalid_time() {
+        let scan = SyntheticScan::default();
+        let bytes = SyntheticScan {
+            start_time: RhixTime {
+                month: 13,
+                ..scan.start_time
+            },
+            ..scan
+        }
+        .to_bytes();
+        let header = RhixHeader::read(&bytes[..], Endian::Little).unwrap();
+        let offset = header.utc_offset().unwrap();
+        assert!(matches!(
+            header.timing.start_time.to_utc(offset),
+            Err(ConvertError::InvalidTime(RhixTime { month: 13, .. }))
+        ));
+        assert!(matches!(
+            crate::read_rhix(&bytes),
+            Err(ConvertError::InvalidTime(_))
+        ));
+    }
+
+    #[test]
+    fn signal_flag_words_decode() {
+        let decoded = [0, 1, 2, 0b111, 0x8000].map(SignalFlags::from_bits);
+        let flags = |clutter_filter, attenuation_correction, unknown| SignalFlags {
+            clutter_filter,
+            attenuation_correction,
+            unknown,
+        };
+        assert_eq!(
+            decoded,
+            [
+                flags(false, false, 0),
+                flags(true, false, 0),
+                flags(false, true, 0),
+                flags(true, true, 0b100),
+                flags(false, false, 0x8000),
+            ]
+        );
+
+        let scan = SyntheticScan {
+            signal_flag: 0b11,
+            ..Default::default()
+        };
+        let header = RhixHeader::read(&scan.to_bytes()[..], Endian::Little).unwrap();
+        assert_eq!(header.signal_flag, flags(true, true, 0));
+    }
+
+    #[test]
+    fn radar_constants_round_trip() {
+        let scan = SyntheticScan {
+            radar_const_h: 2500,
+            radar_const_v: -300,
+            ..Default::default()
+        };
+        let header = RhixHeader::read(&scan.to_bytes()[..], Endian::Little).unwrap();
+        assert_eq!(header.calibration.radar_const_h, 2500);
+        assert_eq!(header.calibration.radar_const_v, -300);
+    }
+
+    #[test]
+    fn product_number_and_model_type_round_trip() {
+        let scan = SyntheticScan {
+            product_number: 7,
+            model_type: 2120,
+            ..Default::default()
+        };
+        let header = RhixHeader::read(&scan.to_bytes()[..], Endian::Little).unwrap();
+        assert_eq!(header.site.product_number, 7);
+        assert_eq!(header.site.model_type, 2120);
+    }
+}
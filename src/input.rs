@@ -0,0 +1,226 @@
+use crate::{ConvertError, Endian, ReadOptions, RhixHeader};
+use std::io::{BufRead, Read};
+
+/// Compression applied to an input file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    Rhix,
+    Gz,
+    Zst,
+    Bz2,
+}
+
+impl InputFormat {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "rhix" => Some(InputFormat::Rhix),
+            "gz" => Some(InputFormat::Gz),
+            "zst" => Some(InputFormat::Zst),
+            "bz2" => Some(InputFormat::Bz2),
+            _ => None,
+        }
+    }
+
+    /// The format whose magic bytes start `bytes`, with the little or big endian header size
+    /// of 156 taken as uncompressed `.rhix`. `None` when they match none.
+    pub fn from_magic(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [0x1f, 0x8b, ..] => Some(InputFormat::Gz),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(InputFormat::Zst),
+            [b'B', b'Z', b'h', ..] => Some(InputFormat::Bz2),
+            [0x9c, 0x00, ..] | [0x00, 0x9c, ..] => Some(InputFormat::Rhix),
+            _ => None,
+        }
+    }
+}
+
+impl std::str::FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        InputFormat::from_extension(s)
+            .ok_or_else(|| format!("Unknown input format [{s}], expected rhix, gz, zst or bz2"))
+    }
+}
+
+/// Parses a possibly compressed stream as it is decompressed, without holding the whole file
+/// in memory. `name` names the radar unless a gzip header stores a filename.
+///
+/// The compression is detected from the first bytes, so `format` only applies to a stream
+/// whose magic bytes are not recognized, see [`InputFormat::from_magic`].
+pub fn read_stream(
+    reader: impl Read,
+    format: InputFormat,
+    mut name: String,
+    options: &ReadOptions,
+) -> Result<(RhixHeader, silv::RadarFile), ConvertError> {
+    let mut reader = std::io::BufReader::new(reader);
+    let (header, mut radar) = match detect(&mut reader, format)? {
+        InputFormat::Gz => {
+            let mut decoder = flate2::read::GzDecoder::new(reader);
+            let parsed = crate::read_rhix_from_reader(&mut decoder, options)?;
+            name = stored_name(&decoder).unwrap_or(name);
+            parsed
+        }
+        InputFormat::Zst => {
+            crate::read_rhix_from_reader(zstd::stream::read::Decoder::new(reader)?, options)?
+        }
+        InputFormat::Bz2 => {
+            crate::read_rhix_from_reader(bzip2::read::BzDecoder::new(reader), options)?
+        }
+        InputFormat::Rhix => crate::read_rhix_from_reader(reader, options)?,
+    };
+    radar.name = name;
+
+    Ok((header, radar))
+}
+
+/// File name without its compression and `.rhix` extensions.
+pub fn scan_stem(file_name: &str) -> &str {
+    let mut stem = file_name;
+    for ext in [".gz", ".zst", ".bz2", ".rhix"] {
+        stem = stem.strip_suffix(ext).unwrap_or(stem);
+    }
+
+    stem
+}
+
+/// The name [`crate::read_file`] gives the scan in a file on disk, from the filename stored in
+/// its gzip header when it has one and from the path otherwise. Nothing past a gzip header
+/// is read.
+pub fn scan_name(path: impl AsRef<std::path::Path>) -> Result<String, ConvertError> {
+    let path = path.as_ref();
+    let format = path_format(path)?;
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let name = scan_stem(&file_name).to_string();
+
+    let mut file = std::io::BufReader::new(std::fs::File::open(path)?);
+    match detect(&mut file, format)? {
+        InputFormat::Gz => Ok(stored_name(&flate2::read::GzDecoder::new(file)).unwrap_or(name)),
+        _ => Ok(name),
+    }
+}
+
+/// Scan name from the filename stored in a gzip header, if any.
+fn stored_name(decoder: &flate2::read::GzDecoder<impl Read>) -> Option<String> {
+    let stored = decoder.header()?.filename()?;
+    Some(scan_stem(&String::from_utf8_lossy(stored)).to_string())
+}
+
+/// Reads only the header of a file on disk, decompressing no further than needed.
+pub fn read_header(
+    path: impl AsRef<std::path::Path>,
+    endian: Endian,
+) -> Result<RhixHeader, ConvertError> {
+    let path = path.as_ref();
+    read_stream_header(std::fs::File::open(path)?, path_format(path)?, endian)
+}
+
+/// Reads only the header of a possibly compressed stream, detecting the compression like
+/// [`read_stream`].
+pub fn read_stream_header(
+    reader: impl Read,
+    format: InputFormat,
+    endian: Endian,
+) -> Result<RhixHeader, ConvertError> {
+    let mut reader = std::io::BufReader::new(reader);
+    match detect(&mut reader, format)? {
+        InputFormat::Gz => RhixHeader::read(flate2::read::GzDecoder::new(reader), endian),
+        InputFormat::Zst => RhixHeader::read(zstd::stream::read::Decoder::new(reader)?, endian),
+        InputFormat::Bz2 => RhixHeader::read(bzip2::read::BzDecoder::new(reader), endian),
+        InputFormat::Rhix => RhixHeader::read(reader, endian),
+    }
+}
+
+/// Format of a stream from its first bytes, so mislabeled files are still read. `named` is
+/// what the file name or caller says, used when the bytes are inconclusive.
+fn detect(reader: &mut impl BufRead, named: InputFormat) -> std::io::Result<InputFormat> {
+    let detected = InputFormat::from_magic(reader.fill_buf()?).unwrap_or(named);
+    if detected != named {
+        log::info!("Input named as {named:?} starts like {detected:?}, reading it as such");
+    }
+    Ok(detected)
+}
+
+pub(crate) fn path_format(path: &std::path::Path) -> Result<InputFormat, ConvertError> {
+    let extension = path.extension().map(|ex| ex.to_string_lossy());
+    extension
+        .as_deref()
+        .and_then(InputFormat::from_extension)
+        .ok_or_else(|| ConvertError::UnknownExtension(extension.as_deref().map(Into::into)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SyntheticScan;
+    use std::io::Write;
+
+    /// Writes `bytes` to a file named `name` in a directory of its own.
+    fn temp_file(test: &str, name: &str, bytes: &[u8]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("rhix_converter_{test}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn gzip(bytes: &[u8], stored: Option<&str>) -> Vec<u8> {
+        let mut builder = flate2::GzBuilder::new();
+        if let Some(stored) = stored {
+            builder = builder.filename(stored);
+        }
+        let mut encoder = builder.write(Vec::new(), flate2::Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn scan_name_prefers_the_gzip_stored_filename() {
+        let bytes = SyntheticScan::default().to_bytes();
+        let stored = temp_file("scan_name", "upload.gz", &gzip(&bytes, Some("0500.rhix")));
+        assert_eq!(scan_name(&stored).unwrap(), "0500");
+        let (_, radar) = crate::read_file(&stored, &ReadOptions::default()).unwrap();
+        assert_eq!(radar.name, "0500");
+
+        let unnamed = temp_file("scan_name", "0600.rhix.gz", &gzip(&bytes, None));
+        assert_eq!(scan_name(unnamed).unwrap(), "0600");
+        let raw = temp_file("scan_name", "0700.rhix", &bytes);
+        assert_eq!(scan_name(raw).unwrap(), "0700");
+    }
+
+    #[test]
+    fn compression_is_detected_from_magic_bytes() {
+        let bytes = SyntheticScan::default().to_bytes();
+        let options = ReadOptions::default();
+
+        // Gzip named as raw, and raw named as gzip
+        for (data, named) in [
+            (gzip(&bytes, None), InputFormat::Rhix),
+            (bytes.clone(), InputFormat::Gz),
+        ] {
+            let (_, radar) = read_stream(&data[..], named, "scan".into(), &options).unwrap();
+            assert_eq!(radar.sweeps[0].rays.len(), 360);
+        }
+
+        let mislabeled = temp_file("magic", "scan.rhix", &gzip(&bytes, None));
+        let header = read_header(&mislabeled, Endian::Little).unwrap();
+        assert_eq!(header.geometry.gates, 1000);
+    }
+
+    #[test]
+    fn magic_bytes() {
+        assert_eq!(
+            InputFormat::from_magic(&[0x1f, 0x8b, 8]),
+            Some(InputFormat::Gz)
+        );
+        assert_eq!(
+            InputFormat::from_magic(&[0x28, 0xb5, 0x2f, 0xfd]),
+            Some(InputFormat::Zst)
+        );
+        assert_eq!(InputFormat::from_magic(b"BZh9"), Some(InputFormat::Bz2));
+        assert_eq!(InputFormat::from_magic(&[0x9c, 0]), Some(InputFormat::Rhix));
+        assert_eq!(InputFormat::from_magic(&[0, 0x9c]), Some(InputFormat::Rhix));
+        assert_eq!(InputFormat::from_magic(b"PK"), None);
+    }
+}
@@ -0,0 +1,1396 @@
+use std::io::Read;
+
+macro_rules! read_num {
+    ($reader:expr, $ty:ty, $endian:expr) => {{
+        let mut buf = [0u8; std::mem::size_of::<$ty>()];
+        $reader.read_exact(&mut buf)?;
+        match $endian {
+            $crate::Endian::Little => <$ty>::from_le_bytes(buf),
+            $crate::Endian::Big => <$ty>::from_be_bytes(buf),
+        }
+    }};
+}
+
+mod azimuth;
+#[cfg(feature = "cfradial")]
+mod cfradial;
+mod dealias;
+mod export;
+mod fixture;
+mod gaps;
+mod geo;
+mod header;
+mod input;
+mod moment;
+#[cfg(feature = "odim")]
+mod odim;
+#[cfg(feature = "python")]
+mod python;
+mod snr;
+mod stats;
+mod volume;
+
+pub use azimuth::{resample_azimuth, sort_azimuth, AzimuthConvention};
+#[cfg(feature = "cfradial")]
+pub use cfradial::write_cfradial;
+pub use dealias::dealias;
+pub use export::{gate_ranges, trim_range, write_csv};
+pub use fixture::SyntheticScan;
+pub use gaps::fill_gaps;
+pub use geo::gate_location;
+pub use header::{
+    Correction, Endian, ObservationMode, Polarization, Pulse, RadarCalibration, RecordItem,
+    RhixHeader, RhixTime, ScanGeometry, SignalFlags, Site, Timing, HEADER_SIZE, KNOWN_VERSIONS,
+};
+pub use input::{read_header, read_stream, read_stream_header, scan_name, scan_stem, InputFormat};
+pub use moment::{Calibration, Moment, MomentInfo, VelocitySign, MOMENTS};
+#[cfg(feature = "odim")]
+pub use odim::write_odim;
+pub use snr::{default_snr_threshold, mask_low_snr};
+pub use stats::{moment_stats, MomentStats};
+pub use volume::{merge_volumes, split_sweeps};
+
+#[derive(Debug)]
+pub enum ConvertError {
+    Io(std::io::Error),
+    UnknownExtension(Option<String>),
+    HeaderSize(u16),
+    AngleBlockSize(u16),
+    UnknownVersion(u16),
+    InvalidTime(RhixTime),
+    InvalidTimezone(i16),
+    NoMoments,
+    MissingMoment(Moment),
+    TruncatedRayBlock(u16),
+    TruncatedHeader(usize),
+    TruncatedRay {
+        remaining: usize,
+        expected: usize,
+    },
+    NoRays,
+    ObservedBlockSize {
+        size: u16,
+        gates: u16,
+        recorded: u16,
+        fits: usize,
+    },
+    ElevationDeviation {
+        elevation: f32,
+        fixed: f32,
+    },
+    NonzeroReserved {
+        field: &'static str,
+        value: u64,
+    },
+    ImplausibleGates {
+        gates: u16,
+        gate_res: u16,
+    },
+}
+
+impl std::fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConvertError::Io(e) => write!(f, "{e}"),
+            ConvertError::UnknownExtension(Some(ex)) => write!(f, "Unknown file type [{ex}]"),
+            ConvertError::UnknownExtension(None) => write!(f, "Unknown file type"),
+            ConvertError::HeaderSize(size) if size.swap_bytes() == HEADER_SIZE => {
+                write!(
+                    f,
+                    "Header size is {size}, not 156, may have the wrong endianness."
+                )
+            }
+            ConvertError::HeaderSize(size) => {
+                write!(f, "Header size is {size}, not 156, may have wrong format.")
+            }
+            ConvertError::AngleBlockSize(size) => {
+                write!(f, "Angle information block size error, found {size}")
+            }
+            ConvertError::UnknownVersion(version) => write!(
+                f,
+                "Unknown format version {version}, expected one of {KNOWN_VERSIONS:?}"
+            ),
+            ConvertError::InvalidTime(t) => write!(
+                f,
+                "Invalid date {}-{:02}-{:02} {:02}:{:02}:{:02}",
+                t.year, t.month, t.day, t.hour, t.minute, t.second
+            ),
+            ConvertError::InvalidTimezone(minutes) => {
+                write!(f, "Timezone offset of {minutes} minutes is out of range")
+            }
+            ConvertError::NoMoments => write!(f, "record_item does not enable any moments"),
+            ConvertError::MissingMoment(moment) => {
+                write!(f, "Moment {moment} was requested but is not recorded")
+            }
+            ConvertError::TruncatedRayBlock(size) => {
+                write!(f, "Truncated ray block, block size {size} is too small")
+            }
+            ConvertError::TruncatedHeader(size) => {
+                write!(
+                    f,
+                    "File is {size} bytes, too short for the {HEADER_SIZE} byte header"
+                )
+            }
+            ConvertError::TruncatedRay {
+                remaining,
+                expected,
+            } => write!(
+                f,
+                "File ends {remaining} bytes into a ray, a full ray is {expected} bytes"
+            ),
+            ConvertError::NoRays => write!(f, "File has a header but no rays"),
+            ConvertError::ObservedBlockSize {
+                size,
+                gates,
+                recorded,
+                fits,
+            } => {
+                write!(
+                    f,
+                    "Observed block error, record_item says {recorded} moments, block size \
+                     {size} fits {fits} of {gates} gates"
+                )
+            }
+            ConvertError::NonzeroReserved { field, value } => write!(
+                f,
+                "Reserved header field {field} is {value:#x}, not 0, may be another format version"
+            ),
+            ConvertError::ImplausibleGates { gates, gate_res } => write!(
+                f,
+                "Implausible range geometry of {gates} gates {gate_res} m apart, may be misread"
+            ),
+            ConvertError::ElevationDeviation { elevation, fixed } => write!(
+                f,
+                "Ray elevation {elevation:.2} is far from the sweep's {fixed:.2}, may be misaligned"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConvertError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConvertError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ConvertError {
+    fn from(e: std::io::Error) -> Self {
+        ConvertError::Io(e)
+    }
+}
+
+/// Reads only the observation mode from an uncompressed `.rhix` buffer in byte order `endian`.
+pub fn observation_mode(data: &[u8], endian: Endian) -> Result<ObservationMode, ConvertError> {
+    Ok(RhixHeader::read(data, endian)?.geometry.observation_mode)
+}
+
+/// Settings that control how a file is parsed.
+#[derive(Debug, Clone)]
+pub struct ReadOptions {
+    /// Elevation jump in degrees between consecutive PPI rays that starts a new sweep.
+    pub sweep_threshold: f32,
+    /// Furthest a PPI ray's elevation may be from its sweep's fixed angle, in degrees. A lone
+    /// ray split off between two sweeps at the same angle is held to it too.
+    pub elevation_tolerance: f32,
+    /// Keep the quality channel as the "QC" moment when the file records it.
+    pub quality: bool,
+    /// Only keep these moments. `None` keeps every recorded moment.
+    pub moments: Option<Vec<Moment>>,
+    /// No-data codes that replace a moment's default from [`MomentInfo::no_data`]. `None`
+    /// decodes every raw value of that moment.
+    pub no_data: std::collections::HashMap<Moment, Option<u16>>,
+    /// Scalings that replace a moment's built-in offset and scale.
+    pub calibration: std::collections::HashMap<Moment, Calibration>,
+    /// Keep the raw counts of every moment instead of decoding them. No-data codes are kept as
+    /// recorded rather than becoming NaN, and each moment's description ends with the scaling
+    /// and no-data code that would have been applied, such as
+    /// `Reflectivity, raw (N - 32768) * 0.01 dBZ, no data 0`.
+    pub raw: bool,
+    /// Add the header's azimuth offset to PPI ray azimuths so they are relative to true north.
+    pub azimuth_offset: bool,
+    /// Convention of ray azimuths in the output. An RHI ray's angle is its elevation as if it
+    /// were a meteorological azimuth measured from the zenith, so the horizon is at 90.
+    pub azimuth_convention: AzimuthConvention,
+    /// Direction of positive radial velocities. [`VelocitySign::Toward`] negates every decoded
+    /// velocity and notes it in the moment's description. Raw counts are left as recorded.
+    pub velocity_sign: VelocitySign,
+    /// Fail on anomalies that are otherwise only logged as warnings:
+    ///
+    /// - a format version outside [`KNOWN_VERSIONS`]
+    /// - a moment in `moments` that the file does not record
+    /// - trailing bytes after the last complete ray, which are otherwise dropped
+    /// - a file without a single ray, which otherwise converts to a radar file with no sweeps
+    /// - an angle block larger than the 6 bytes of azimuth and elevation, whose extra bytes are
+    ///   otherwise skipped
+    /// - a reserved header field that is not zero, see [`RhixHeader::nonzero_reserved`]
+    /// - no gates, more than 10000, or gates less than 1 or more than 1000 m apart
+    /// - a PPI ray further than `elevation_tolerance` from its sweep's fixed angle, a sign of
+    ///   misread rays
+    ///
+    /// It also warns when a single polarization scan records dual-pol moments. Gates reaching
+    /// past the unambiguous range of the PRF, whose echoes may be range folded, are warned about
+    /// in every mode. Gates holding a no-data code are ordinary data and become NaN either way.
+    pub strict: bool,
+    /// Byte order of every number in the file. WR2120 files are little endian, big endian
+    /// ones come from byte swapping transfer tools.
+    pub endian: Endian,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        ReadOptions {
+            sweep_threshold: 0.1,
+            elevation_tolerance: 1.0,
+            quality: true,
+            moments: None,
+            no_data: std::collections::HashMap::new(),
+            calibration: std::collections::HashMap::new(),
+            raw: false,
+            azimuth_offset: true,
+            azimuth_convention: AzimuthConvention::Meteorological,
+            velocity_sign: VelocitySign::Away,
+            strict: false,
+            endian: Endian::Little,
+        }
+    }
+}
+
+/// Reads and parses a raw `.rhix` file, or a `.gz`, `.zst` or `.bz2` one as it is decompressed.
+/// The radar is named after the filename stored in the gzip header when present,
+/// and after the path without its extensions otherwise.
+pub fn read_file(
+    path: impl AsRef<std::path::Path>,
+    options: &ReadOptions,
+) -> Result<(RhixHeader, silv::RadarFile), ConvertError> {
+    let path = path.as_ref();
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let name = scan_stem(&file_name).to_string();
+    read_stream(
+        std::fs::File::open(path)?,
+        input::path_format(path)?,
+        name,
+        options,
+    )
+}
+
+/// Parses an uncompressed WR2120 `.rhix` buffer into its header and a radar file.
+///
+/// ```no_run
+/// let bytes = std::fs::read("scan.rhix")?;
+/// let (header, radar) = rhix_converter::read_rhix(&bytes)?;
+/// println!("{} sweeps at tx frequency {}", radar.sweeps.len(), header.calibration.tx_frequency);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn read_rhix(data: &[u8]) -> Result<(RhixHeader, silv::RadarFile), ConvertError> {
+    read_rhix_with(data, &ReadOptions::default())
+}
+
+/// Parses an uncompressed WR2120 `.rhix` buffer with the given options.
+pub fn read_rhix_with(
+    data: &[u8],
+    options: &ReadOptions,
+) -> Result<(RhixHeader, silv::RadarFile), ConvertError> {
+    read_rhix_from_reader(data, options)
+}
+
+/// Parses an uncompressed WR2120 `.rhix` stream with the given options, one ray at a time.
+///
+/// Any reader works, such as a socket or an embedded buffer, and the file system is never
+/// touched. Compressed streams go through [`read_stream`] instead, which [`read_file`] uses.
+///
+/// ```no_run
+/// let stream = std::net::TcpStream::connect("radar.local:9000")?;
+/// let options = rhix_converter::ReadOptions::default();
+/// let (_, radar) = rhix_converter::read_rhix_from_reader(stream, &options)?;
+/// println!("{} sweeps", radar.sweeps.len());
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn read_rhix_from_reader(
+    reader: impl Read,
+    options: &ReadOptions,
+) -> Result<(RhixHeader, silv::RadarFile), ConvertError> {
+    Parser::new().read(reader, options)
+}
+
+/// Reusable parser state for converting many files in turn, such as in a long running service.
+///
+/// The buffers a ray is read into are kept between calls instead of being allocated afresh for
+/// every file. A parser holds no reference to what it last read, so it can be moved between
+/// threads, or kept one per thread for parsing in parallel.
+///
+/// ```no_run
+/// let mut parser = rhix_converter::Parser::new();
+/// let options = rhix_converter::ReadOptions::default();
+/// for path in ["first.rhix", "second.rhix"] {
+///     let (_, radar) = parser.read(std::fs::File::open(path)?, &options)?;
+///     println!("{} sweeps", radar.sweeps.len());
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct Parser {
+    /// The ray being read.
+    block: Vec<u8>,
+    /// Bytes read past the end of a short ray.
+    carry: Vec<u8>,
+    /// Ray elevations of the sweep being read.
+    elevations: Vec<f32>,
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses an uncompressed `.rhix` stream like [`read_rhix_from_reader`].
+    // Docs: https://www.manualslib.com/manual/1935797/Furuno-Wr2120.html?page=72#manual
+    pub fn read(
+        &mut self,
+        mut reader: impl Read,
+        options: &ReadOptions,
+    ) -> Result<(RhixHeader, silv::RadarFile), ConvertError> {
+        let header = RhixHeader::read(&mut reader, options.endian)?;
+        log::debug!("Header {header:?}");
+
+        if !KNOWN_VERSIONS.contains(&header.version) {
+            if options.strict {
+                return Err(ConvertError::UnknownVersion(header.version));
+            }
+            log::warn!(
+                "Unknown format version {}, fields may be misread",
+                header.version
+            );
+        }
+        for (field, value) in header.nonzero_reserved() {
+            if options.strict {
+                return Err(ConvertError::NonzeroReserved { field, value });
+            }
+            log::warn!("{}", ConvertError::NonzeroReserved { field, value });
+        }
+        if !GATES_RANGE.contains(&header.geometry.gates)
+            || !GATE_RES_RANGE.contains(&header.geometry.gate_res)
+        {
+            let error = ConvertError::ImplausibleGates {
+                gates: header.geometry.gates,
+                gate_res: header.geometry.gate_res,
+            };
+            if options.strict {
+                return Err(error);
+            }
+            log::warn!("{error}");
+        }
+        let extent = header.pulse.tx_pulse_blind_len as f32
+            + header.geometry.gates as f32 * header.geometry.gate_res as f32;
+        if let Some(max_range) = header.pulse.max_range.filter(|&max| extent > max) {
+            log::warn!("Gates reach {extent} m, past the {max_range:.0} m unambiguous range");
+        }
+
+        if options.strict && header.calibration.polarization == Polarization::Single {
+            for moment in Moment::DUAL_POL.into_iter().filter(|&m| header.records(m)) {
+                log::warn!("{moment} is recorded but the scan is single polarization");
+            }
+        }
+
+        if header.clutter_ref_file.is_unset() {
+            log::warn!(
+                "No clutter reference time is set, clutter filtering may not have been applied"
+            );
+        }
+
+        if header.pulse.pulse_switchpoint != 0 {
+            log::warn!(
+                "Pulse switchpoint {} is set, gates are assumed to be {} m apart on both pulses",
+                header.pulse.pulse_switchpoint,
+                header.geometry.gate_res
+            );
+        }
+
+        let (lat, lon) = (header.site.latitude, header.site.longitude);
+        let (nyquist, gates, gate_res) = (
+            header.pulse.nyquist,
+            header.geometry.gates,
+            header.geometry.gate_res,
+        );
+        let observation_mode = header.geometry.observation_mode;
+        let azimuth_offset = if options.azimuth_offset {
+            header.site.azimuth_offset as f32 / 100.0
+        } else {
+            0.0
+        };
+        let utc_offset = header.utc_offset()?;
+        let start_time = header.timing.start_time.to_utc(utc_offset)?;
+
+        let mut radar = silv::RadarFile {
+            name: "FWLX".into(),
+            sweeps: Vec::new(),
+            params: std::collections::HashMap::new(),
+        };
+
+        let stored = header.stored_moments();
+        let recorded = stored.len() as u16;
+        if recorded == 0 {
+            return Err(ConvertError::NoMoments);
+        }
+        if let Some(moments) = &options.moments {
+            for &moment in moments {
+                if !header.records(moment) {
+                    if options.strict {
+                        return Err(ConvertError::MissingMoment(moment));
+                    }
+                    log::warn!("Moment {moment} was requested but is not recorded");
+                }
+            }
+        }
+
+        // Moments that are recorded but not wanted are still read so the stream stays aligned
+        let wanted = |moment: Moment| {
+            header.records(moment)
+                && (moment != Moment::Quality || options.quality)
+                && options
+                    .moments
+                    .as_ref()
+                    .is_none_or(|moments| moments.contains(&moment))
+        };
+
+        let keep = Moment::ALL.map(wanted);
+        let no_data = Moment::ALL.map(|moment| match options.no_data.get(&moment) {
+            Some(&no_data) => no_data,
+            None => moment.info().no_data,
+        });
+        let calibration = Moment::ALL.map(|moment| match options.calibration.get(&moment) {
+            Some(&calibration) => calibration,
+            None => moment.calibration(),
+        });
+        let sign = Moment::ALL.map(|moment| match (moment, options.velocity_sign) {
+            (Moment::Velocity, VelocitySign::Toward) => -1.0,
+            _ => 1.0,
+        });
+
+        for moment in Moment::ALL {
+            if header.records(moment) && !wanted(moment) {
+                log::debug!("Skipping moment {moment}");
+            }
+
+            if wanted(moment) {
+                let (description, units) = match options.raw {
+                    true => {
+                        let Calibration { offset, scale } = calibration[moment.bit()];
+                        let no_data = match no_data[moment.bit()] {
+                            Some(code) => code.to_string(),
+                            None => "none".into(),
+                        };
+                        let description = format!(
+                            "{}, raw (N - {offset}) * {scale} {}, no data {no_data}",
+                            moment.description(),
+                            moment.units()
+                        );
+                        (description, "raw".into())
+                    }
+                    false
+                        if moment == Moment::Velocity
+                            && options.velocity_sign == VelocitySign::Toward =>
+                    {
+                        let description =
+                            format!("{}, positive toward the radar", moment.description());
+                        (description, moment.units().into())
+                    }
+                    false => (moment.description().into(), moment.units().into()),
+                };
+                radar.params.insert(
+                    moment.name().into(),
+                    silv::ParamDescription {
+                        description,
+                        units,
+                        meters_to_first_cell: header.pulse.tx_pulse_blind_len as f32,
+                        meters_between_cells: gate_res as f32,
+                    },
+                );
+            }
+        }
+
+        // The site altitude is left out: the silv git dependency is not pinned, and no revision
+        // with an altitude field has been checked. CSV, CfRadial and ODIM output take it from
+        // the header instead.
+        let new_sweep = || silv::Sweep {
+            latitude: lat,
+            longitude: lon,
+            elevation: 0.0,
+            nyquist_velocity: nyquist,
+            ..Default::default()
+        };
+
+        let mut sweep = new_sweep();
+        let Parser {
+            block,
+            carry,
+            elevations,
+        } = self;
+        elevations.clear();
+        let mut last_elevation: Option<f32> = None;
+        // Angle block, observed block size and the gates of every recorded moment
+        let ray_bytes = 8 + recorded as usize * gates as usize * 2;
+        // One ray is read at a time, so the decompressed file is never held in memory
+        block.clear();
+        block.resize(ray_bytes, 0);
+        let endian = options.endian;
+        let gate = |bytes: [u8; 2]| match endian {
+            Endian::Little => u16::from_le_bytes(bytes),
+            Endian::Big => u16::from_be_bytes(bytes),
+        };
+        // Further angle blocks are told apart from the data block by their size
+        let supplementary = (2 + recorded as u32 * gates as u32 * 2) != ANGLE_BLOCK_SIZE as u32;
+        // Bytes read past the end of a ray holding fewer moments than recorded start the next one
+        carry.clear();
+        let mut warned_short = false;
+        'rays: loop {
+            block.truncate(ray_bytes);
+            let filled = fill_carried(carry, &mut reader, block)?;
+            if filled == 0 {
+                break;
+            }
+            if filled < ray_bytes {
+                // Once rays are seen to hold fewer moments, the last one is shorter than a full ray
+                let complete = warned_short && filled >= 8 && {
+                    let size = read_num!(&block[..], u16, options.endian);
+                    let observed = read_num!(&block[6..], u16, options.endian) as usize;
+                    size == ANGLE_BLOCK_SIZE && 6 + observed <= filled
+                };
+                if !complete {
+                    truncated(filled, ray_bytes, options.strict)?;
+                    break;
+                }
+                block.truncate(filled);
+            }
+
+            let size = read_num!(&block[..], u16, options.endian);
+            if size != ANGLE_BLOCK_SIZE && (options.strict || size < ANGLE_BLOCK_SIZE) {
+                return Err(ConvertError::AngleBlockSize(size));
+            }
+            // Angle fields added after the elevation by later firmware are skipped
+            let extra = (size - ANGLE_BLOCK_SIZE) as usize;
+            if extra > 0 {
+                block.resize(ray_bytes + extra, 0);
+                let filled = fill_carried(carry, &mut reader, &mut block[ray_bytes..])?;
+                if filled < extra {
+                    truncated(ray_bytes + filled, ray_bytes + extra, options.strict)?;
+                    break;
+                }
+            }
+            let mut angles = &block[2..];
+            let mut azimuth = read_num!(angles, u16, options.endian) as f32 / 100.0;
+            let mut elevation = read_num!(angles, u16, options.endian) as f32 / 100.0;
+
+            // A ray may have more angle blocks before its data, such as its end angles, in which
+            // case it is taken to point midway between the first and the last
+            let mut data_start = ANGLE_BLOCK_SIZE as usize + extra;
+            let mut end = None;
+            while supplementary
+                && read_num!(&block[data_start..], u16, options.endian) == ANGLE_BLOCK_SIZE
+            {
+                let len = block.len();
+                block.resize(len + ANGLE_BLOCK_SIZE as usize, 0);
+                let filled = fill_carried(carry, &mut reader, &mut block[len..])?;
+                if filled < ANGLE_BLOCK_SIZE as usize {
+                    truncated(len + filled, block.len(), options.strict)?;
+                    break 'rays;
+                }
+
+                let mut angles = &block[data_start + 2..];
+                let end_azimuth = read_num!(angles, u16, options.endian) as f32 / 100.0;
+                let end_elevation = read_num!(angles, u16, options.endian) as f32 / 100.0;
+                end = Some((end_azimuth, end_elevation));
+                data_start += ANGLE_BLOCK_SIZE as usize;
+            }
+            if let Some((end_azimuth, end_elevation)) = end {
+                // Half the turn from start to end, the short way round
+                let turn = (end_azimuth - azimuth + 540.0) % 360.0 - 180.0;
+                azimuth = (azimuth + turn / 2.0).rem_euclid(360.0);
+                elevation = (elevation + end_elevation) / 2.0;
+            }
+            let mut data = &block[data_start..];
+
+            if observation_mode == ObservationMode::Ppi
+                && last_elevation
+                    .is_some_and(|last| (elevation - last).abs() > options.sweep_threshold)
+            {
+                let finished = std::mem::replace(&mut sweep, new_sweep());
+                push_sweep(&mut radar, finished, elevations, observation_mode, options)?;
+                elevations.clear();
+            }
+
+            last_elevation = Some(elevation);
+            elevations.push(elevation);
+
+            let angle = match observation_mode {
+                ObservationMode::Ppi => azimuth + azimuth_offset,
+                _ => -elevation + 90.0,
+            };
+            let mut ray = silv::Ray {
+                azimuth: options.azimuth_convention.convert(angle),
+                time: start_time,
+                data: std::collections::HashMap::default(),
+            };
+
+            let observed_block_size = read_num!(data, u16, options.endian);
+
+            let moment_bytes = observed_block_size
+                .checked_sub(2)
+                .ok_or(ConvertError::TruncatedRayBlock(observed_block_size))?;
+            let fits = match gates {
+                0 => recorded as usize,
+                _ => moment_bytes as usize / (gates as usize * 2),
+            };
+            let error = ConvertError::ObservedBlockSize {
+                size: observed_block_size,
+                gates,
+                recorded,
+                fits,
+            };
+            if fits > recorded as usize || fits == 0 {
+                return Err(error);
+            }
+            if fits < recorded as usize {
+                if options.strict {
+                    return Err(error);
+                }
+                if !warned_short {
+                    log::warn!("{error}, reading the first {fits} of every such ray");
+                    warned_short = true;
+                }
+                let ray_end = block.len() - data.len() + moment_bytes as usize;
+                carry.extend_from_slice(&block[ray_end..]);
+            }
+
+            for &moment in stored.iter().take(fits) {
+                // The whole ray is known to be present, so gates are decoded in place
+                let (raw, rest) = data.split_at(gates as usize * 2);
+                data = rest;
+                if keep[moment.bit()] {
+                    let (no_data, calibration) = (no_data[moment.bit()], calibration[moment.bit()]);
+                    let sign = sign[moment.bit()];
+                    let values = match options.raw {
+                        true => raw
+                            .chunks_exact(2)
+                            .map(|v| gate([v[0], v[1]]) as f64)
+                            .collect(),
+                        false => raw
+                            .chunks_exact(2)
+                            .map(|v| calibration.decode(gate([v[0], v[1]]), no_data))
+                            .map(|v| sign * moment.clamp(v))
+                            .collect(),
+                    };
+                    ray.data.insert(moment.name().into(), values);
+                }
+            }
+
+            sweep.rays.push(ray);
+        }
+
+        push_sweep(&mut radar, sweep, elevations, observation_mode, options)?;
+        if radar.sweeps.is_empty() {
+            if options.strict {
+                return Err(ConvertError::NoRays);
+            }
+            log::warn!("File has no rays");
+        }
+
+        let end_time = header.timing.end_time.to_utc(utc_offset).ok();
+        if let Some(end_time) = end_time.filter(|&end| end > start_time) {
+            interpolate_times(&mut radar, start_time, end_time);
+        } else if header.geometry.rotation_speed > 0.0 {
+            rotation_times(&mut radar, start_time, header.geometry.rotation_speed);
+        }
+
+        Ok((header, radar))
+    }
+}
+
+/// Size of an angle block, its own size field followed by an azimuth and an elevation.
+const ANGLE_BLOCK_SIZE: u16 = 6;
+
+/// Gate counts that a header is expected to give. A WR2120 reaches about 70 km, or 1400 gates
+/// of 50 m.
+const GATES_RANGE: std::ops::RangeInclusive<u16> = 1..=10000;
+
+/// Gate spacings in meters that a header is expected to give.
+const GATE_RES_RANGE: std::ops::RangeInclusive<u16> = 1..=1000;
+
+/// Fails on a file ending `filled` bytes into a ray of `expected` bytes when `strict`, or warns
+/// that the partial ray is dropped.
+fn truncated(filled: usize, expected: usize, strict: bool) -> Result<(), ConvertError> {
+    if strict {
+        return Err(ConvertError::TruncatedRay {
+            remaining: filled,
+            expected,
+        });
+    }
+    log::warn!("Dropping {filled} trailing bytes, a full ray is {expected} bytes");
+    Ok(())
+}
+
+/// Fills `buf` like [`fill`], starting with the bytes carried over from the previous ray.
+fn fill_carried(carry: &mut Vec<u8>, reader: impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let carried = carry.len().min(buf.len());
+    buf[..carried].copy_from_slice(&carry[..carried]);
+    carry.drain(..carried);
+    Ok(carried + fill(reader, &mut buf[carried..])?)
+}
+
+/// Reads until `buf` is full or the stream ends, returning how many bytes were read.
+pub(crate) fn fill(mut reader: impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(filled)
+}
+
+/// Spreads ray times evenly from `start` for the first ray to `end` for the last, to the
+/// nanosecond so rays within the same second stay apart.
+fn interpolate_times(
+    radar: &mut silv::RadarFile,
+    start: chrono::DateTime<chrono::Utc>,
+    end: chrono::DateTime<chrono::Utc>,
+) {
+    let rays = radar.sweeps.iter().map(|s| s.rays.len()).sum::<usize>();
+    if rays < 2 {
+        return;
+    }
+
+    // Nanoseconds only overflow an i64 after 292 years, and the product is taken as i128
+    let span = (end - start).num_nanoseconds().unwrap_or(i64::MAX) as i128;
+    let all_rays = radar.sweeps.iter_mut().flat_map(|s| &mut s.rays);
+    for (i, ray) in all_rays.enumerate() {
+        let offset = span * i as i128 / (rays - 1) as i128;
+        ray.time = start + chrono::Duration::nanoseconds(offset as i64);
+    }
+}
+
+/// Times rays from `start` by the angle the antenna turned to reach them at `speed` degrees per
+/// second, for files without a usable end time. The turn between rays is taken the short way
+/// around, so either rotation direction works. The move between sweeps is not accounted for.
+fn rotation_times(radar: &mut silv::RadarFile, start: chrono::DateTime<chrono::Utc>, speed: f32) {
+    let mut turned = 0.0;
+    let mut last: Option<f32> = None;
+    for ray in radar.sweeps.iter_mut().flat_map(|s| &mut s.rays) {
+        if let Some(last) = last {
+            let d = (ray.azimuth - last).rem_euclid(360.0);
+            turned += d.min(360.0 - d) as f64;
+        }
+        last = Some(ray.azimuth);
+
+        let offset = turned / speed as f64 * 1e9;
+        ray.time = start + chrono::Duration::nanoseconds(offset.round() as i64);
+    }
+}
+
+fn push_sweep(
+    radar: &mut silv::RadarFile,
+    mut sweep: silv::Sweep,
+    elevations: &[f32],
+    observation_mode: ObservationMode,
+    options: &ReadOptions,
+) -> Result<(), ConvertError> {
+    // Only the last sweep can be empty, when the file ends before its first ray
+    if sweep.rays.is_empty() {
+        return Ok(());
+    }
+
+    // A PPI holds its elevation fixed, so the mean only smooths out antenna jitter
+    if observation_mode == ObservationMode::Ppi {
+        let fixed = elevations.iter().sum::<f32>() / elevations.len() as f32;
+        sweep.elevation = fixed;
+
+        let tolerance = options.elevation_tolerance;
+        let deviates = |elevation: f32, fixed: f32| -> Result<(), ConvertError> {
+            if options.strict {
+                return Err(ConvertError::ElevationDeviation { elevation, fixed });
+            }
+            log::warn!("{}", ConvertError::ElevationDeviation { elevation, fixed });
+            Ok(())
+        };
+
+        let furthest = elevations
+            .iter()
+            .copied()
+            .max_by(|a, b| (a - fixed).abs().total_cmp(&(b - fixed).abs()));
+        if let Some(elevation) = furthest.filter(|e| (e - fixed).abs() > tolerance) {
+            deviates(elevation, fixed)?;
+        }
+
+        // A single rogue ray jumps past sweep_threshold and back, becoming a sweep of its own
+        if let [.., before, lone] = &radar.sweeps[..] {
+            let rejoined = (before.elevation - fixed).abs() <= tolerance;
+            if lone.rays.len() == 1 && rejoined && (lone.elevation - fixed).abs() > tolerance {
+                deviates(lone.elevation, fixed)?;
+            }
+        }
+    }
+
+    radar.sweeps.push(sweep);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::offset;
+    use chrono::TimeZone;
+
+    /// Four rays of ten gates, small enough to lay out by hand.
+    fn scan() -> SyntheticScan {
+        SyntheticScan {
+            rays: 4,
+            gates: 10,
+            ..Default::default()
+        }
+    }
+
+    fn read(bytes: &[u8]) -> silv::RadarFile {
+        read_rhix(bytes).unwrap().1
+    }
+
+    fn strict() -> ReadOptions {
+        ReadOptions {
+            strict: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ppi_sweep_elevation_is_the_ray_elevation() {
+        let scan = SyntheticScan {
+            elevation: 2.4,
+            ..scan()
+        };
+        let radar = read(&scan.to_bytes());
+        assert_eq!(radar.sweeps.len(), 1);
+        assert!((radar.sweeps[0].elevation - 2.4).abs() < 0.01);
+    }
+
+    #[test]
+    fn rhi_sweep_elevation_is_not_set() {
+        let scan = SyntheticScan {
+            elevation: 30.0,
+            observation_mode: 2,
+            ..scan()
+        };
+        assert_eq!(read(&scan.to_bytes()).sweeps[0].elevation, 0.0);
+    }
+
+    #[test]
+    fn elevation_jumps_start_new_sweeps() {
+        let scan = SyntheticScan { rays: 6, ..scan() };
+        let mut bytes = scan.to_bytes();
+        for ray in 3..6 {
+            scan.patch(&mut bytes, scan.ray_offset(ray) + 4, 150);
+        }
+
+        let radar = read(&bytes);
+        let sweeps: Vec<_> = radar
+            .sweeps
+            .iter()
+            .map(|s| (s.elevation, s.rays.len()))
+            .collect();
+        assert_eq!(sweeps, [(0.5, 3), (1.5, 3)]);
+
+        let options = ReadOptions {
+            sweep_threshold: 2.0,
+            ..Default::default()
+        };
+        let (_, radar) = read_rhix_with(&bytes, &options).unwrap();
+        assert_eq!(radar.sweeps.len(), 1);
+    }
+
+    #[test]
+    fn short_observed_block_is_an_error() {
+        let scan = scan();
+        let mut bytes = scan.to_bytes();
+        scan.patch(&mut bytes, scan.ray_offset(0) + 6, 1);
+        assert!(matches!(
+            read_rhix(&bytes),
+            Err(ConvertError::TruncatedRayBlock(1))
+        ));
+    }
+
+    #[test]
+    fn no_recorded_moments_is_an_error() {
+        let scan = scan();
+        let mut bytes = scan.to_bytes();
+        scan.patch(&mut bytes, offset::RECORD_ITEM, 0);
+        assert!(matches!(read_rhix(&bytes), Err(ConvertError::NoMoments)));
+    }
+
+    #[test]
+    fn ray_times_span_start_to_end() {
+        let radar = read(&scan().to_bytes());
+        let times: Vec<_> = radar.sweeps[0].rays.iter().map(|r| r.time).collect();
+        assert!(times.windows(2).all(|t| t[0] < t[1]));
+
+        let start = chrono::Utc.with_ymd_and_hms(2023, 5, 1, 12, 0, 0).unwrap();
+        assert_eq!(times[0], start);
+        assert_eq!(times[3], start + chrono::Duration::seconds(60));
+    }
+
+    #[test]
+    fn timezone_shifts_ray_times_to_utc() {
+        let scan = |timezone| SyntheticScan { timezone, ..scan() };
+        // UTC+9:00
+        let radar = read(&scan(540).to_bytes());
+        let start = chrono::Utc.with_ymd_and_hms(2023, 5, 1, 3, 0, 0).unwrap();
+        assert_eq!(radar.sweeps[0].rays[0].time, start);
+
+        assert!(matches!(
+            read_rhix(&scan(-900).to_bytes()),
+            Err(ConvertError::InvalidTimezone(-900))
+        ));
+    }
+
+    #[test]
+    fn unknown_version_fails_only_under_strict() {
+        let bytes = SyntheticScan {
+            version: 4,
+            ..scan()
+        }
+        .to_bytes();
+        assert!(matches!(
+            read_rhix_with(&bytes, &strict()),
+            Err(ConvertError::UnknownVersion(4))
+        ));
+        let (header, radar) = read_rhix(&bytes).unwrap();
+        assert_eq!(header.version, 4);
+        assert_eq!(radar.sweeps[0].rays.len(), 4);
+    }
+
+    #[test]
+    fn truncated_final_ray_is_dropped() {
+        let scan = scan();
+        let mut bytes = scan.to_bytes_with(|ray, _, _| 32768 + ray);
+        bytes.truncate(bytes.len() - 5);
+
+        let rays = &read(&bytes).sweeps[0].rays;
+        assert_eq!(rays.len(), 3);
+        for (i, ray) in rays.iter().enumerate() {
+            assert_eq!(ray.azimuth, i as f32 * 90.0);
+            assert_eq!(ray.data["REF"], [i as f64 * 0.01; 10]);
+        }
+
+        assert!(matches!(
+            read_rhix_with(&bytes, &strict()),
+            Err(ConvertError::TruncatedRay {
+                remaining: 83,
+                expected: 88
+            })
+        ));
+    }
+
+    #[test]
+    fn azimuth_offset_shifts_and_wraps() {
+        // 95 degrees
+        let bytes = SyntheticScan {
+            azimuth_offset: 9500,
+            ..scan()
+        }
+        .to_bytes();
+
+        let azimuths = |options: &ReadOptions| -> Vec<f32> {
+            let (_, radar) = read_rhix_with(&bytes, options).unwrap();
+            radar.sweeps[0].rays.iter().map(|r| r.azimuth).collect()
+        };
+        assert_eq!(azimuths(&ReadOptions::default()), [95.0, 185.0, 275.0, 5.0]);
+
+        let options = ReadOptions {
+            azimuth_offset: false,
+            ..Default::default()
+        };
+        assert_eq!(azimuths(&options), [0.0, 90.0, 180.0, 270.0]);
+    }
+
+    #[test]
+    fn no_data_gates_are_nan_unless_disabled() {
+        let scan = scan();
+        let bytes = scan.to_bytes_with(|_, _, gate| match gate {
+            0 => 0,
+            _ => 32768,
+        });
+
+        let radar = read(&bytes);
+        for values in radar.sweeps[0].rays[0].data.values() {
+            assert!(values[0].is_nan());
+            assert!(!values[1].is_nan());
+        }
+
+        let options = ReadOptions {
+            no_data: [
+                (Moment::Reflectivity, None),
+                (Moment::Velocity, Some(32768)),
+            ]
+            .into(),
+            ..Default::default()
+        };
+        let (_, radar) = read_rhix_with(&bytes, &options).unwrap();
+        let data = &radar.sweeps[0].rays[0].data;
+        assert_eq!(data["REF"][0], -327.68);
+        assert_eq!(data["VEL"][0], -327.68);
+        assert!(data["VEL"][1].is_nan());
+    }
+
+    #[test]
+    fn moments_are_read_in_stored_order() {
+        // SyntheticScan stores moments in the same assumed order the reader expects, so this
+        // only checks that reading follows stored_moments rather than the listed order
+        let scan = SyntheticScan {
+            moments: vec![Moment::Rho, Moment::Reflectivity, Moment::SpectrumWidth],
+            ..scan()
+        };
+        // Every moment's gates hold its own bit, so a misassigned block shows up as another value
+        let raw = |moment: Moment| 32768 + moment.bit() as u16;
+        let bytes = scan.to_bytes_with(|_, moment, _| raw(moment));
+
+        let data = &read(&bytes).sweeps[0].rays[0].data;
+        assert_eq!(data.len(), 3);
+        for moment in scan.moments {
+            assert_eq!(data[moment.name()], [moment.decode(raw(moment)); 10]);
+        }
+    }
+
+    #[test]
+    fn rotation_speed_times_rays_without_an_end_time() {
+        let scan = SyntheticScan {
+            end_time: scan().start_time,
+            ..scan()
+        };
+
+        let start = chrono::Utc.with_ymd_and_hms(2023, 5, 1, 12, 0, 0).unwrap();
+        let offsets = |bytes: &[u8]| -> Vec<i64> {
+            let rays = &read(bytes).sweeps[0].rays;
+            rays.iter()
+                .map(|r| (r.time - start).num_milliseconds())
+                .collect()
+        };
+        // 90 degrees apart at 18 degrees per second
+        assert_eq!(offsets(&scan.to_bytes()), [0, 5000, 10000, 15000]);
+
+        let stopped = SyntheticScan {
+            rotation_speed: 0,
+            ..scan
+        };
+        assert_eq!(offsets(&stopped.to_bytes()), [0; 4]);
+    }
+
+    #[test]
+    fn reads_from_a_cursor() {
+        let scan = scan();
+        let cursor = std::io::Cursor::new(scan.to_bytes());
+        let (header, radar) = read_rhix_from_reader(cursor, &ReadOptions::default()).unwrap();
+        assert_eq!(header.geometry.rays, 4);
+        assert_eq!(radar.sweeps[0].rays.len(), 4);
+        assert_eq!(radar.sweeps[0].rays[3].data["REF"].len(), 10);
+    }
+
+    #[test]
+    fn short_and_header_only_files() {
+        let bytes = scan().to_bytes();
+        for len in [0, 100] {
+            assert!(matches!(
+                read_rhix(&bytes[..len]),
+                Err(ConvertError::TruncatedHeader(l)) if l == len
+            ));
+        }
+
+        let header_only = &bytes[..HEADER_SIZE as usize];
+        assert!(read(header_only).sweeps.is_empty());
+        assert!(matches!(
+            read_rhix_with(header_only, &strict()),
+            Err(ConvertError::NoRays)
+        ));
+    }
+
+    #[test]
+    fn big_endian_reads_the_same_values() {
+        let little = scan();
+        let big = SyntheticScan {
+            endian: Endian::Big,
+            ..scan()
+        };
+        let value =
+            |ray: u16, moment: Moment, gate: u16| 32768 + ray * 16 + moment.bit() as u16 + gate;
+        let options = ReadOptions {
+            endian: Endian::Big,
+            ..Default::default()
+        };
+        let (header, radar) = read_rhix_with(&big.to_bytes_with(value), &options).unwrap();
+        let expected = read(&little.to_bytes_with(value));
+
+        assert_eq!(header.site.latitude, 35.0);
+        assert_eq!(header.calibration.tx_frequency, 9_400_000);
+        assert_eq!(radar.sweeps[0].rays.len(), 4);
+        for (ray, expected) in radar.sweeps[0].rays.iter().zip(&expected.sweeps[0].rays) {
+            assert_eq!(ray.azimuth, expected.azimuth);
+            assert_eq!(ray.data, expected.data);
+        }
+
+        assert!(matches!(
+            read_rhix(&big.to_bytes()),
+            Err(ConvertError::HeaderSize(0x9c00))
+        ));
+    }
+
+    /// The bytes of `scan` with `extra` more fields in every ray after its elevation, each
+    /// holding `0xffff`, in blocks whose size covers them.
+    fn with_angle_fields(scan: &SyntheticScan, bytes: &[u8], extra: u16) -> Vec<u8> {
+        let mut out = bytes[..scan.ray_offset(0)].to_vec();
+        for ray in 0..scan.rays {
+            let start = scan.ray_offset(ray);
+            let size = 6 + 2 * extra;
+            out.extend(size.to_le_bytes());
+            out.extend(&bytes[start + 2..start + 6]);
+            out.extend(std::iter::repeat_n(0xff, 2 * extra as usize));
+            out.extend(&bytes[start + 6..scan.ray_offset(ray + 1)]);
+        }
+        out
+    }
+
+    #[test]
+    fn larger_angle_block_is_skipped() {
+        let scan = scan();
+        let value = |ray: u16, _, gate: u16| 32768 + ray * 10 + gate;
+        let bytes = with_angle_fields(&scan, &scan.to_bytes_with(value), 1);
+
+        let rays = &read(&bytes).sweeps[0].rays;
+        assert_eq!(rays.len(), 4);
+        for (i, ray) in rays.iter().enumerate() {
+            assert_eq!(ray.azimuth, i as f32 * 90.0);
+            assert_eq!(ray.data["REF"][1], (i * 10 + 1) as f64 * 0.01);
+        }
+
+        assert!(matches!(
+            read_rhix_with(&bytes, &strict()),
+            Err(ConvertError::AngleBlockSize(8))
+        ));
+    }
+
+    #[test]
+    fn rogue_ray_elevation_is_caught() {
+        let scan = SyntheticScan { rays: 5, ..scan() };
+        let mut bytes = scan.to_bytes();
+        scan.patch(&mut bytes, scan.ray_offset(2) + 4, 500);
+
+        // It splits off into a sweep of its own, and its deviation is only warned about
+        let radar = read(&bytes);
+        let rays: Vec<_> = radar.sweeps.iter().map(|s| s.rays.len()).collect();
+        assert_eq!(rays, [2, 1, 2]);
+
+        assert!(matches!(
+            read_rhix_with(&bytes, &strict()),
+            Err(ConvertError::ElevationDeviation { elevation, fixed })
+                if elevation == 5.0 && fixed == 0.5
+        ));
+    }
+
+    #[test]
+    fn end_angle_blocks_give_the_midpoint() {
+        let scan = SyntheticScan { rays: 2, ..scan() };
+        let mut bytes = scan.to_bytes();
+        scan.patch(&mut bytes, scan.ray_offset(0) + 2, 35000);
+
+        // A second angle block with the end azimuth and elevation follows each start block. The
+        // first ray turns from 350 to 10 degrees through north, the second from 180 to 190.
+        let ends = [(1000u16, 50u16), (19000, 50)];
+        let mut with_ends = bytes[..scan.ray_offset(0)].to_vec();
+        for (ray, (azimuth, elevation)) in (0..2).zip(ends) {
+            let start = scan.ray_offset(ray);
+            with_ends.extend(&bytes[start..start + 6]);
+            for v in [6, azimuth, elevation] {
+                with_ends.extend(v.to_le_bytes());
+            }
+            with_ends.extend(&bytes[start + 6..scan.ray_offset(ray + 1)]);
+        }
+
+        let rays = &read(&with_ends).sweeps[0].rays;
+        let angles: Vec<_> = rays.iter().map(|r| r.azimuth).collect();
+        assert_eq!(angles, [0.0, 185.0]);
+        assert_eq!(rays[1].data["REF"].len(), 10);
+    }
+
+    #[test]
+    fn rays_holding_fewer_moments_than_recorded() {
+        let scan = SyntheticScan {
+            moments: vec![Moment::Reflectivity, Moment::Velocity],
+            ..scan()
+        };
+        let mut bytes = scan.to_bytes();
+        // record_item also claims ZDR and RHO, which the rays do not hold
+        let claimed = [
+            Moment::Reflectivity,
+            Moment::Velocity,
+            Moment::Zdr,
+            Moment::Rho,
+        ];
+        scan.patch(
+            &mut bytes,
+            offset::RECORD_ITEM,
+            claimed.iter().fold(0, |bits, m| bits | 1 << m.bit()),
+        );
+
+        let rays = &read(&bytes).sweeps[0].rays;
+        assert_eq!(rays.len(), 4);
+        assert!(rays
+            .iter()
+            .all(|r| r.data.len() == 2 && r.data["VEL"].len() == 10));
+
+        let Err(error) = read_rhix_with(&bytes, &strict()) else {
+            panic!("a block short of moments passed under strict");
+        };
+        assert_eq!(
+            error.to_string(),
+            "Observed block error, record_item says 4 moments, block size 42 fits 2 of 10 gates"
+        );
+    }
+
+    #[test]
+    fn ray_times_are_subsecond() {
+        let scan = SyntheticScan {
+            rays: 361,
+            ..scan()
+        };
+        let rays = &read(&scan.to_bytes()).sweeps[0].rays;
+        // 360 steps over the minute, a sixth of a second each
+        let step = rays[1].time - rays[0].time;
+        assert_eq!(step.num_nanoseconds(), Some(166_666_666));
+        assert!(rays.windows(2).all(|r| r[0].time < r[1].time));
+        assert_eq!(rays[3].time.timestamp_subsec_millis(), 500);
+    }
+
+    #[test]
+    fn nonzero_reserved_bytes_fail_only_under_strict() {
+        let scan = scan();
+        let padded = SyntheticScan {
+            start_time: RhixTime {
+                padding: 1,
+                ..scan.start_time
+            },
+            ..scan.clone()
+        }
+        .to_bytes();
+        let Err(error) = read_rhix_with(&padded, &strict()) else {
+            panic!("nonzero start time padding passed under strict");
+        };
+        assert!(matches!(
+            error,
+            ConvertError::NonzeroReserved {
+                field: "start_time.padding",
+                value: 1
+            }
+        ));
+        assert!(read_rhix(&padded).is_ok());
+
+        let mut reserved = scan.to_bytes();
+        scan.patch(&mut reserved, offset::RESERVED, 5);
+        let Err(error) = read_rhix_with(&reserved, &strict()) else {
+            panic!("nonzero reserved bytes passed under strict");
+        };
+        assert!(matches!(
+            error,
+            ConvertError::NonzeroReserved {
+                field: "reserved",
+                value: 5
+            }
+        ));
+        assert!(read_rhix(&reserved).is_ok());
+    }
+
+    #[test]
+    fn reused_parser_reads_the_same_scan_twice() {
+        let value =
+            |ray: u16, moment: Moment, gate: u16| 32768 + ray * 16 + moment.bit() as u16 + gate;
+        let bytes = scan().to_bytes_with(value);
+        let other = SyntheticScan {
+            rays: 7,
+            gates: 3,
+            ..scan()
+        }
+        .to_bytes_with(value);
+        let options = ReadOptions::default();
+
+        let mut parser = Parser::new();
+        let (_, first) = parser.read(bytes.as_slice(), &options).unwrap();
+        parser.read(other.as_slice(), &options).unwrap();
+        let (_, second) = parser.read(bytes.as_slice(), &options).unwrap();
+
+        assert_eq!(first.sweeps.len(), second.sweeps.len());
+        for (first, second) in first.sweeps.iter().zip(&second.sweeps) {
+            assert_eq!(first.elevation, second.elevation);
+            assert_eq!(first.rays.len(), second.rays.len());
+            for (first, second) in first.rays.iter().zip(&second.rays) {
+                assert_eq!(first.azimuth, second.azimuth);
+                assert_eq!(first.time, second.time);
+                assert_eq!(first.data, second.data);
+            }
+        }
+    }
+
+    #[test]
+    fn velocity_sign_flips_decoded_velocities() {
+        let scan = SyntheticScan {
+            moments: vec![Moment::Velocity],
+            ..scan()
+        };
+        let bytes = scan.to_bytes_with(|_, _, _| 32768 + 500);
+        let toward = ReadOptions {
+            velocity_sign: VelocitySign::Toward,
+            ..Default::default()
+        };
+
+        let away = read(&bytes);
+        assert_eq!(away.sweeps[0].rays[0].data["VEL"][0], 5.0);
+        let (_, toward) = read_rhix_with(&bytes, &toward).unwrap();
+        assert_eq!(toward.sweeps[0].rays[0].data["VEL"][0], -5.0);
+        assert!(toward.params["VEL"]
+            .description
+            .ends_with(", positive toward the radar"));
+        assert!(!away.params["VEL"].description.contains("toward"));
+    }
+
+    #[test]
+    fn implausible_gate_spacing_fails_only_under_strict() {
+        for gate_res in [0, 2000] {
+            let bytes = SyntheticScan { gate_res, ..scan() }.to_bytes();
+            let Err(error) = read_rhix_with(&bytes, &strict()) else {
+                panic!("a gate spacing of {gate_res} m passed under strict");
+            };
+            assert!(matches!(
+                error,
+                ConvertError::ImplausibleGates { gates: 10, gate_res: res } if res == gate_res
+            ));
+            assert!(read_rhix(&bytes).is_ok());
+        }
+    }
+
+    #[test]
+    fn observation_mode_follows_the_byte_order() {
+        let scan = SyntheticScan {
+            observation_mode: 2,
+            endian: Endian::Big,
+            ..scan()
+        };
+        let bytes = scan.to_bytes();
+        assert_eq!(
+            observation_mode(&bytes, Endian::Big).unwrap(),
+            ObservationMode::Rhi
+        );
+        assert!(!matches!(
+            observation_mode(&bytes, Endian::Little),
+            Ok(ObservationMode::Rhi)
+        ));
+    }
+}
@@ -1,262 +1,1705 @@
 #![allow(non_snake_case)]
-use chrono::TimeZone;
 use clap::Parser;
-use std::io::Read;
-
-macro_rules! readle {
-    ($reader:expr, $ty:ty) => {{
-        let mut buf = [0u8; std::mem::size_of::<$ty>()];
-        $reader.read_exact(&mut buf).unwrap();
-        let x = <$ty>::from_le_bytes(buf);
-        x
-    }};
-
-    ($reader:expr, $ty:ty, $length:expr) => {{
-        let mut buf = vec![0u8; std::mem::size_of::<$ty>() * $length];
-        $reader.read_exact(&mut buf).unwrap();
-        buf.chunks_exact(std::mem::size_of::<$ty>())
-            .map(|v| <$ty>::from_le_bytes(v.try_into().unwrap()))
-            .collect::<Vec<$ty>>()
-    }};
-}
-
-fn read_data(v: u16, data_type: &str) -> f64 {
-    match data_type {
-        "R" | "REF" | "VEL" | "ZDR" | "KDP" => (v as f64 - 32768.0) / 100.0,
-        "PHI" => 360.0 * (v as f64 - 32768.0) / 65535.0,
-        "RHO" => 2.0 * (v as f64 - 1.0) / 65534.0,
-        "SW" => (v as f64 - 1.0) / 100.0,
-        d => panic!("Unknown datatype [{d}]"),
-    }
-}
-
-
-// Docs: https://www.manualslib.com/manual/1935797/Furuno-Wr2120.html?page=72#manual
-fn read_file(path: impl AsRef<std::path::Path>) {
-    let path = path.as_ref();
-    let mut data = &*{
-        let bytes = std::fs::read(path).unwrap();
-        match path.extension().map(|ex| ex.to_str().unwrap()) {
-            Some("gz") => {
-                let mut buf = Vec::new();
-                flate2::read::GzDecoder::new(&*bytes)
-                    .read_to_end(&mut buf)
-                    .unwrap();
-                buf
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::io::IsTerminal;
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+    Silv,
+    Csv,
+    #[cfg(feature = "cfradial")]
+    Cfradial,
+    #[cfg(feature = "odim")]
+    Odim,
+}
+
+/// What to do with a file whose output already exists.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum IfExists {
+    /// Leave the existing output and do not read the file.
+    Skip,
+    Overwrite,
+    /// Fail the file.
+    Error,
+    /// Add the new sweeps to the existing output, leaving out any at an elevation it already
+    /// has. Only CSV output can be read back, as silv cannot read its own files.
+    Append,
+}
+
+/// Order to process input files in.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum SortBy {
+    Name,
+    /// Scan start time from the header. Files without a readable header go last, by name.
+    Time,
+}
+
+/// Compression applied to written output files.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Compress {
+    None,
+    Gz,
+    Zst,
+}
+
+impl Compress {
+    /// Extension appended to compressed file names.
+    fn extension(self) -> Option<&'static str> {
+        match self {
+            Compress::None => None,
+            Compress::Gz => Some("gz"),
+            Compress::Zst => Some("zst"),
+        }
+    }
+}
+
+#[derive(Parser)]
+struct Args {
+    /// Path(s) of file to convert. For a folder, use a * symbol at the end. Use - for stdin.
+    #[clap(short, long, value_parser)]
+    files: String,
+
+    /// Treat --files as a directory and convert every supported file under it, at any depth.
+    #[clap(short, long)]
+    recursive: bool,
+
+    /// Only process the first N matched files, in --sort-by order.
+    #[clap(long)]
+    limit: Option<usize>,
+
+    /// Order to process matched files in, which also decides what --limit keeps.
+    #[clap(long, value_enum, default_value_t = SortBy::Name)]
+    sort_by: SortBy,
+
+    /// Extensions of the files to convert. Other files matched by --files are skipped with a
+    /// warning.
+    #[clap(long, value_delimiter = ',', default_value = "rhix,gz,zst,bz2,zip")]
+    extensions: Vec<String>,
+
+    /// Compression of data read from stdin: rhix, gz, zst or bz2.
+    #[clap(long, value_parser, default_value = "rhix")]
+    input_format: rhix_converter::InputFormat,
+
+    /// Directory to write converted files into. Created if it does not exist.
+    #[clap(short, long, value_parser, default_value = ".")]
+    output_dir: String,
+
+    /// Radar name to write, which also names the output files. `{stem}` expands to the input
+    /// name, which is the default, and `{model}` and `{product}` to the header's model type and
+    /// product number. Keep `{stem}` in it when converting several files, e.g. {model}_{stem},
+    /// so their outputs do not overwrite each other.
+    #[clap(long, value_parser)]
+    name: Option<String>,
+
+    /// What to do when a file's output already exists in the output directory.
+    #[clap(long, value_enum, default_value_t = IfExists::Overwrite)]
+    if_exists: IfExists,
+
+    /// Format to write converted files in.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Silv)]
+    output_format: OutputFormat,
+
+    /// Compress every output file, adding .gz or .zst to its name. The header dump is left as is.
+    #[clap(long, value_enum, default_value_t = Compress::None)]
+    compress: Compress,
+
+    /// Rename moments in the output as MOMENT=NAME, such as REF=DBZH,VEL=VRADH. Moments a file
+    /// does not record are warned about, and a NAME that another moment keeps fails the file.
+    #[clap(long, value_parser = parse_rename, value_delimiter = ',')]
+    rename: Vec<(rhix_converter::Moment, String)>,
+
+    /// Add gate latitude, longitude and height columns to CSV output.
+    #[clap(long)]
+    csv_locations: bool,
+
+    /// Value written for missing gates in CSV and CfRadial output, such as -9999. CSV leaves
+    /// them empty and CfRadial writes NaN by default. silv and ODIM output keep their own
+    /// missing values and ignore it.
+    #[clap(long, allow_negative_numbers = true)]
+    fill_value: Option<f64>,
+
+    /// Elevation change in degrees between rays that starts a new sweep.
+    #[clap(long, value_parser, default_value_t = 0.1)]
+    sweep_threshold: f32,
+
+    /// Furthest a PPI ray's elevation may be from its sweep's angle before it is reported as
+    /// misread, in degrees. An error with --strict.
+    #[clap(long, value_parser, default_value_t = 1.0)]
+    elevation_tolerance: f32,
+
+    /// Skip the quality channel instead of writing it as the QC moment.
+    #[clap(long)]
+    no_quality: bool,
+
+    /// Comma separated moments to keep, e.g. REF,VEL. Keeps every recorded moment by default.
+    #[clap(long, value_parser, value_delimiter = ',')]
+    moments: Vec<rhix_converter::Moment>,
+
+    /// Skip scans that start before this RFC 3339 time, e.g. 2023-05-01T00:00:00Z.
+    #[clap(long, value_parser)]
+    start: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Skip scans that start after this RFC 3339 time.
+    #[clap(long, value_parser)]
+    end: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Add the header's antenna azimuth offset to PPI azimuths. Pass false to keep them raw.
+    #[clap(long, action = clap::ArgAction::Set, default_value_t = true)]
+    apply_azi_offset: bool,
+
+    /// Write azimuths as meteorological, clockwise from north, or mathematical, counterclockwise
+    /// from east.
+    #[clap(long, default_value = "meteorological")]
+    azimuth_convention: rhix_converter::AzimuthConvention,
+
+    /// Write radial velocities as positive away from the radar, as recorded, or toward it.
+    #[clap(long, default_value = "away")]
+    velocity_sign: rhix_converter::VelocitySign,
+
+    /// Shift every ray time, and the times --start and --end compare against, by this many
+    /// seconds to correct a drifting clock. May be negative or fractional, up to a year either
+    /// way.
+    #[clap(long, value_parser = parse_time_offset, allow_negative_numbers = true)]
+    time_offset: Option<chrono::Duration>,
+
+    /// Byte order of the input files, le or be for files byte swapped in transfer.
+    #[clap(long, default_value = "le")]
+    endian: rhix_converter::Endian,
+
+    /// Override a moment's no-data code as MOMENT=RAW, or MOMENT=none to decode every value.
+    /// Repeat for several moments.
+    #[clap(long, value_parser = parse_no_data)]
+    no_data: Vec<(rhix_converter::Moment, Option<u16>)>,
+
+    /// TOML file replacing the built-in scaling of some moments, as a table per moment name
+    /// with an offset and a scale, e.g. `[REF]` with `offset = 32768` and `scale = 0.01`.
+    #[clap(long, value_parser)]
+    calibration: Option<std::path::PathBuf>,
+
+    /// Store the raw counts of every moment instead of physical values. Each moment's
+    /// description records the scaling and no-data code, which is kept as its count.
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "dealias",
+            "snr_threshold",
+            "recalibrate",
+            "fill_gaps",
+            "velocity_sign",
+        ]
+    )]
+    raw: bool,
+
+    /// Also write every parsed header field, and values derived from them, to <name>.json in
+    /// the output directory, grouped into timing, site, calibration, pulse, geometry and
+    /// correction objects. The JSON is indented unless --compact is passed.
+    #[clap(long)]
+    dump_header: bool,
+
+    /// Write the --dump-header JSON on a single line.
+    #[clap(long)]
+    compact: bool,
+
+    /// Number of files to convert at once. Defaults to the available parallelism.
+    #[clap(short, long, value_parser)]
+    jobs: Option<usize>,
+
+    /// Hide the progress bar. It is also hidden when stdout is not a terminal.
+    #[clap(short, long)]
+    quiet: bool,
+
+    /// Log more detail, repeat for more. RUST_LOG overrides this.
+    #[clap(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Write a JSON list of every input file with its status and the scans and output files
+    /// converted from it to this path once the run is done.
+    #[clap(long)]
+    manifest: Option<std::path::PathBuf>,
+
+    /// Print a one line summary of every file's header instead of converting it.
+    #[clap(long)]
+    list: bool,
+
+    /// Print the minimum, maximum and mean of every moment, with its valid and missing gate
+    /// counts, as a line of JSON per scan. The line is prefixed with the scan's pulses per ray,
+    /// which the spread of velocity and spectrum width depends on. Hides the progress bar.
+    #[clap(long)]
+    stats: bool,
+
+    /// Print a CSV table of how many files record each moment and the time span they cover,
+    /// reading only the headers, instead of converting anything.
+    #[clap(long, conflicts_with = "list")]
+    coverage: bool,
+
+    /// Parse every file without writing any output, reporting which files parse cleanly.
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Check that every file parses fully under --strict without writing any output, printing
+    /// OK or the first failure per file. Exits with an error code if any file fails.
+    #[clap(long)]
+    verify: bool,
+
+    /// Fail files on anomalies that are otherwise only warned about, such as an unknown format
+    /// version, a requested moment that is not recorded or a truncated final ray. The full list
+    /// is in the README and the docs of ReadOptions::strict.
+    #[clap(long)]
+    strict: bool,
+
+    /// Unfold aliased radial velocities using each sweep's Nyquist velocity.
+    #[clap(long)]
+    dealias: bool,
+
+    /// Mask reflectivity below this SNR in dB. Without a value the threshold comes from the
+    /// header's noise and threshold powers.
+    #[clap(long, value_parser, num_args = 0..=1)]
+    snr_threshold: Option<Option<f64>>,
+
+    /// With --snr-threshold, also mask every other moment at the masked gates.
+    #[clap(long)]
+    snr_mask_all: bool,
+
+    /// Merge scans that are tilts of the same volume, by scan_num and total_scans, into one
+    /// output per volume instead of one per file.
+    #[clap(long)]
+    merge_volume: bool,
+
+    /// Write every sweep to its own file, named with a _00, _01, ... sweep index suffix.
+    #[clap(long)]
+    flatten: bool,
+
+    /// Add this many dB to reflectivity, for a radar constant that is off by as much from the
+    /// one stored in the header. Applied after SNR masking, which uses the stored constant.
+    #[clap(long, allow_negative_numbers = true)]
+    recalibrate: Option<f64>,
+
+    /// Drop gates further than this many meters from the radar.
+    #[clap(long)]
+    max_range: Option<f32>,
+
+    /// Drop gates past the unambiguous range of the PRF, whose echoes may be range folded.
+    /// With --max-range the nearer of the two applies.
+    #[clap(long)]
+    trim_folded: bool,
+
+    /// Linearly interpolate across runs of up to this many missing gates within a ray, after
+    /// dealiasing and SNR masking. Off by default since it alters the data.
+    #[clap(long)]
+    fill_gaps: Option<usize>,
+
+    /// Sort each sweep's rays by azimuth instead of keeping acquisition order.
+    #[clap(long)]
+    sort_azimuth: bool,
+
+    /// Only keep the sweep with this index, counting from 0 in acquisition order.
+    #[clap(long, conflicts_with = "select_elevation")]
+    select_sweep: Option<usize>,
+
+    /// Only keep the sweep nearest this elevation in degrees. A file without a sweep within
+    /// 0.2 degrees of it fails.
+    #[clap(long)]
+    select_elevation: Option<f32>,
+
+    /// Resample each PPI sweep onto this many evenly spaced azimuths, from the nearest ray.
+    #[clap(long, value_parser)]
+    resample: Option<usize>,
+}
+
+/// Reads a --calibration file, rejecting tables that do not name a moment.
+fn read_calibration(
+    path: &std::path::Path,
+) -> Result<HashMap<rhix_converter::Moment, rhix_converter::Calibration>, Box<dyn std::error::Error>>
+{
+    let config: HashMap<String, rhix_converter::Calibration> =
+        toml::from_str(&std::fs::read_to_string(path)?)?;
+
+    config
+        .into_iter()
+        .map(|(name, calibration)| Ok((name.parse()?, calibration)))
+        .collect()
+}
+
+fn parse_no_data(s: &str) -> Result<(rhix_converter::Moment, Option<u16>), String> {
+    let (moment, raw) = s
+        .split_once('=')
+        .ok_or_else(|| format!("Expected MOMENT=RAW, found [{s}]"))?;
+    let raw = match raw {
+        "none" => None,
+        raw => Some(
+            raw.parse()
+                .map_err(|e| format!("Invalid no-data code [{raw}]: {e}"))?,
+        ),
+    };
+
+    Ok((moment.parse()?, raw))
+}
+
+fn parse_rename(s: &str) -> Result<(rhix_converter::Moment, String), String> {
+    let (moment, name) = s
+        .split_once('=')
+        .ok_or_else(|| format!("Expected MOMENT=NAME, found [{s}]"))?;
+    if name.is_empty() {
+        return Err(format!("Empty name for moment [{moment}]"));
+    }
+
+    Ok((moment.parse()?, name.to_string()))
+}
+
+fn parse_time_offset(s: &str) -> Result<chrono::Duration, String> {
+    let seconds: f64 = s
+        .parse()
+        .map_err(|e| format!("Invalid time offset [{s}]: {e}"))?;
+    if !seconds.is_finite() {
+        return Err(format!("Invalid time offset [{s}]"));
+    }
+    if seconds.abs() > MAX_TIME_OFFSET_SECONDS {
+        return Err(format!(
+            "Time offset [{s}] is more than {MAX_TIME_OFFSET_SECONDS} seconds, a year"
+        ));
+    }
+
+    chrono::Duration::try_milliseconds((seconds * 1000.0).round() as i64)
+        .ok_or_else(|| format!("Invalid time offset [{s}]"))
+}
+
+/// A converted scan, as listed in the --manifest.
+#[derive(serde::Serialize)]
+struct Written {
+    name: String,
+    start_time: Option<String>,
+    latitude: f32,
+    longitude: f32,
+    model_type: u16,
+    product_number: u16,
+    /// Empty on a dry run. A --merge-volume volume lists its files under its first scan.
+    outputs: Vec<std::path::PathBuf>,
+}
+
+impl Written {
+    fn new(header: &rhix_converter::RhixHeader, radar: &silv::RadarFile) -> Self {
+        let start_time = header
+            .utc_offset()
+            .and_then(|offset| header.timing.start_time.to_utc(offset))
+            .ok();
+        Written {
+            name: radar.name.clone(),
+            start_time: start_time.map(|t| t.format("%Y-%m-%dT%H:%M:%SZ").to_string()),
+            latitude: header.site.latitude,
+            longitude: header.site.longitude,
+            model_type: header.site.model_type,
+            product_number: header.site.product_number,
+            outputs: Vec::new(),
+        }
+    }
+}
+
+/// One input file of the --manifest.
+#[derive(serde::Serialize)]
+struct ManifestEntry<'a> {
+    input: &'a std::path::Path,
+    /// ok, skipped, exists, interrupted, error or write_error.
+    status: &'static str,
+    error: Option<&'a str>,
+    scans: &'a [Written],
+}
+
+enum Outcome {
+    Converted(Vec<Written>),
+    /// Parsed and held back for --merge-volume.
+    Loaded(Vec<(rhix_converter::RhixHeader, silv::RadarFile)>),
+    /// Outside the --start/--end window, or an archive with nothing left to convert where no
+    /// entry's output exists.
+    Skipped,
+    /// Output already exists with --if-exists skip, or an archive with nothing left to convert
+    /// where it does for some entry.
+    Exists,
+    /// Header summaries for --list, a line per scan labeled with its path, or its path in the
+    /// archive.
+    Listed(Vec<(String, String)>),
+    /// Headers kept for the --coverage table, more than one for an archive.
+    Headers(Vec<rhix_converter::RhixHeader>),
+    /// Not started, as the run was interrupted.
+    Interrupted,
+}
+
+/// Why a file failed. Write failures are counted apart from inputs that did not parse.
+enum Failure {
+    Read(String),
+    /// The input parsed but an output could not be written.
+    Write(String),
+}
+
+impl Failure {
+    fn message(&self) -> &str {
+        match self {
+            Failure::Read(message) | Failure::Write(message) => message,
+        }
+    }
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+/// Whether a scan starts inside the --start/--end window.
+fn in_window(
+    header: &rhix_converter::RhixHeader,
+    args: &Args,
+) -> Result<bool, rhix_converter::ConvertError> {
+    let start_time = header.timing.start_time.to_utc(header.utc_offset()?)?;
+    let start_time = start_time
+        .checked_add_signed(args.time_offset.unwrap_or_else(chrono::Duration::zero))
+        .ok_or(rhix_converter::ConvertError::InvalidTime(
+            header.timing.start_time,
+        ))?;
+    Ok(args.start.is_none_or(|start| start_time >= start)
+        && args.end.is_none_or(|end| start_time <= end))
+}
+
+/// One line summary of a scan for --list.
+fn summary(header: &rhix_converter::RhixHeader) -> Result<String, rhix_converter::ConvertError> {
+    let start_time = header.timing.start_time.to_utc(header.utc_offset()?)?;
+    let moments: Vec<_> = rhix_converter::Moment::ALL
+        .into_iter()
+        .filter(|&m| header.records(m))
+        .map(|m| m.name())
+        .collect();
+
+    Ok(format!(
+        "{:.5},{:.5} {} {} gates of {} m, {} rays, {}",
+        header.site.latitude,
+        header.site.longitude,
+        start_time.format("%Y-%m-%dT%H:%M:%SZ"),
+        header.geometry.gates,
+        header.geometry.gate_res,
+        header.geometry.rays,
+        moments.join(",")
+    ))
+}
+
+/// Reads just enough of a file for --list or --coverage. Files on disk only have their header
+/// read.
+fn list(
+    path: &std::path::Path,
+    args: &Args,
+    options: &rhix_converter::ReadOptions,
+) -> Result<Outcome, Box<dyn std::error::Error>> {
+    let headers = if path.as_os_str() == "-" {
+        let stdin = std::io::stdin().lock();
+        let header = rhix_converter::read_stream(stdin, args.input_format, "FWLX".into(), options)?;
+        vec![(path.display().to_string(), header.0)]
+    } else if is_zip(path) {
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+        let mut headers = Vec::new();
+        for i in 0..archive.len() {
+            let entry = archive.by_index(i)?;
+            let entry_name = entry.name().to_string();
+            let Some(format) = entry_format(&entry_name).filter(|_| !entry.is_dir()) else {
+                continue;
+            };
+            let header = rhix_converter::read_stream_header(entry, format, args.endian)
+                .map_err(|e| format!("{entry_name}: {e}"))?;
+            headers.push((format!("{}/{entry_name}", path.display()), header));
+        }
+        headers
+    } else {
+        let header = rhix_converter::read_header(path, args.endian)?;
+        vec![(path.display().to_string(), header)]
+    };
+
+    let mut kept = Vec::new();
+    for (label, header) in headers {
+        if in_window(&header, args)? {
+            kept.push((label, header));
+        }
+    }
+    if kept.is_empty() {
+        return Ok(Outcome::Skipped);
+    }
+
+    match args.coverage {
+        true => Ok(Outcome::Headers(kept.into_iter().map(|(_, h)| h).collect())),
+        false => Ok(Outcome::Listed(
+            kept.iter()
+                .map(|(label, header)| Ok((label.clone(), summary(header)?)))
+                .collect::<Result<_, rhix_converter::ConvertError>>()?,
+        )),
+    }
+}
+
+/// Prints the --coverage table, a row per moment with the number of files recording it and the
+/// earliest start and latest end time among them. Moments no file records are listed with 0.
+fn print_coverage(headers: &[&rhix_converter::RhixHeader]) {
+    println!("moment,files,first,last");
+    for moment in rhix_converter::Moment::ALL {
+        let times: Vec<_> = headers
+            .iter()
+            .filter(|header| header.records(moment))
+            .filter_map(|header| {
+                let offset = header.utc_offset().ok()?;
+                Some((
+                    header.timing.start_time.to_utc(offset).ok()?,
+                    header.timing.end_time.to_utc(offset).ok()?,
+                ))
+            })
+            .collect();
+        let format = |time: Option<chrono::DateTime<chrono::Utc>>| {
+            time.map(|t| t.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+                .unwrap_or_default()
+        };
+
+        println!(
+            "{},{},{},{}",
+            moment.name(),
+            headers
+                .iter()
+                .filter(|header| header.records(moment))
+                .count(),
+            format(times.iter().map(|t| t.0).min()),
+            format(times.iter().map(|t| t.1).max()),
+        );
+    }
+}
+
+/// Scans read from one input, with the number of archive entries left out because their output
+/// already exists.
+type Scans = (Vec<(rhix_converter::RhixHeader, silv::RadarFile)>, usize);
+
+/// Reads the scans in a file and applies the requested processing. A zip archive holds a scan
+/// per entry and any other input holds one. Scans outside the time window are left out.
+fn load(
+    path: &std::path::Path,
+    args: &Args,
+    options: &rhix_converter::ReadOptions,
+) -> Result<Scans, Box<dyn std::error::Error>> {
+    if is_zip(path) {
+        return load_zip(path, args, options);
+    }
+
+    let stdin = path.as_os_str() == "-";
+    let filtered = args.start.is_some() || args.end.is_some();
+
+    // Only the header is read here, so skipped files are never fully decompressed
+    if filtered && !stdin && !in_window(&rhix_converter::read_header(path, args.endian)?, args)? {
+        return Ok((Vec::new(), 0));
+    }
+
+    let (header, mut radar) = if stdin {
+        let stdin = std::io::stdin().lock();
+        rhix_converter::read_stream(stdin, args.input_format, "FWLX".into(), options)?
+    } else {
+        rhix_converter::read_file(path, options)?
+    };
+
+    if filtered && stdin && !in_window(&header, args)? {
+        return Ok((Vec::new(), 0));
+    }
+
+    postprocess(&header, &mut radar, path, args)?;
+    Ok((vec![(header, radar)], 0))
+}
+
+/// The outcome of an input with no scan left to convert, `existing` of its archive entries left
+/// out for their output.
+fn nothing_loaded(existing: usize) -> Outcome {
+    match existing {
+        0 => Outcome::Skipped,
+        _ => Outcome::Exists,
+    }
+}
+
+fn is_zip(path: &std::path::Path) -> bool {
+    path.extension().is_some_and(|ex| ex == "zip")
+}
+
+/// Reads every supported entry of a zip archive, at any depth, naming each scan after its entry.
+fn load_zip(
+    path: &std::path::Path,
+    args: &Args,
+    options: &rhix_converter::ReadOptions,
+) -> Result<Scans, Box<dyn std::error::Error>> {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+
+    let mut scans = Vec::new();
+    let mut existing = 0;
+    // Scan names so far and the entries they came from
+    let mut names = HashMap::new();
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i)?;
+        if entry.is_dir() {
+            continue;
+        }
+
+        let entry_name = entry.name().to_string();
+        let Some(format) = entry_format(&entry_name) else {
+            log::debug!("Skipping {entry_name} in {}", path.display());
+            continue;
+        };
+        // Entries in nested directories keep their directories in their name, joined by
+        // underscores, so a/scan.rhix and b/scan.rhix do not overwrite each other
+        let (dir, file_name) = entry_name.rsplit_once('/').unwrap_or(("", &entry_name));
+        let prefixed = |name: &str| match dir {
+            "" => name.to_string(),
+            dir => format!("{}_{name}", dir.replace('/', "_")),
+        };
+
+        // Names from a gzip stored filename or from the header are only known once read
+        let mut entry = std::io::BufReader::new(entry);
+        let gzip = rhix_converter::InputFormat::from_magic(std::io::BufRead::fill_buf(&mut entry)?)
+            .unwrap_or(format)
+            == rhix_converter::InputFormat::Gz;
+        let stem = rhix_converter::scan_stem(file_name);
+        let checked_after = name_uses_header(args) || gzip;
+        if !checked_after && skip_existing(&radar_name(&prefixed(stem), None, args), args)? {
+            existing += 1;
+            continue;
+        }
+
+        let (header, mut radar) =
+            rhix_converter::read_stream(entry, format, stem.to_string(), options)
+                .map_err(|e| format!("{entry_name}: {e}"))?;
+        radar.name = prefixed(&radar.name);
+        if !in_window(&header, args)? {
+            continue;
+        }
+        if checked_after && skip_existing(&radar_name(&radar.name, Some(&header), args), args)? {
+            existing += 1;
+            continue;
+        }
+
+        postprocess(&header, &mut radar, path, args)?;
+        // Gzip entries in one directory can still store the same filename
+        if let Some(other) = names.insert(radar.name.clone(), entry_name.clone()) {
+            let name = &radar.name;
+            return Err(format!(
+                "{other} and {entry_name} in {} are both named {name}",
+                path.display()
+            )
+            .into());
+        }
+        scans.push((header, radar));
+    }
+
+    Ok((scans, existing))
+}
+
+/// Input format of a zip entry named `name` from its extension, `None` for other files.
+fn entry_format(name: &str) -> Option<rhix_converter::InputFormat> {
+    std::path::Path::new(name)
+        .extension()
+        .and_then(|ex| ex.to_str())
+        .and_then(rhix_converter::InputFormat::from_extension)
+}
+
+/// Names a parsed scan, keeps only the selected sweep and runs the optional processing steps
+/// on it.
+fn postprocess(
+    header: &rhix_converter::RhixHeader,
+    radar: &mut silv::RadarFile,
+    path: &std::path::Path,
+    args: &Args,
+) -> Result<(), Box<dyn std::error::Error>> {
+    radar.name = radar_name(&radar.name, Some(header), args);
+
+    let selected = if let Some(index) = args.select_sweep {
+        if index >= radar.sweeps.len() {
+            let count = radar.sweeps.len();
+            return Err(format!("No sweep {index}, the scan has {count} sweeps").into());
+        }
+        Some(index)
+    } else if let Some(elevation) = args.select_elevation {
+        let distance = |i: &usize| (radar.sweeps[*i].elevation - elevation).abs();
+        let nearest = (0..radar.sweeps.len())
+            .filter(|i| distance(i) <= SELECT_ELEVATION_TOLERANCE)
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)));
+        let Some(index) = nearest else {
+            let tolerance = SELECT_ELEVATION_TOLERANCE;
+            return Err(format!("No sweep within {tolerance} degrees of {elevation}").into());
+        };
+        Some(index)
+    } else {
+        None
+    };
+    if let Some(index) = selected {
+        let sweep = radar.sweeps.swap_remove(index);
+        radar.sweeps = vec![sweep];
+    }
+
+    if let Some(offset) = args.time_offset {
+        for ray in radar.sweeps.iter_mut().flat_map(|s| &mut s.rays) {
+            ray.time = ray
+                .time
+                .checked_add_signed(offset)
+                .ok_or("--time-offset moves a ray time out of range")?;
+        }
+    }
+
+    let folded = match (args.trim_folded, header.pulse.max_range) {
+        (true, None) => {
+            log::warn!("Not trimming {}, it has no PRF set", path.display());
+            None
+        }
+        (true, max_range) => max_range,
+        (false, _) => None,
+    };
+    let max_range = [args.max_range, folded]
+        .into_iter()
+        .flatten()
+        .reduce(f32::min);
+    if let Some(max_range) = max_range {
+        rhix_converter::trim_range(radar, max_range);
+    }
+
+    if args.dealias {
+        for sweep in &mut radar.sweeps {
+            let nyquist = sweep.nyquist_velocity;
+            rhix_converter::dealias(sweep, nyquist);
+        }
+    }
+
+    if let Some(threshold) = args.snr_threshold {
+        let threshold = threshold.unwrap_or_else(|| rhix_converter::default_snr_threshold(header));
+        for sweep in &mut radar.sweeps {
+            rhix_converter::mask_low_snr(sweep, header, threshold, args.snr_mask_all);
+        }
+    }
+
+    if let Some(delta) = args.recalibrate {
+        let reflectivity = rhix_converter::Moment::Reflectivity.name();
+        let rays = radar.sweeps.iter_mut().flat_map(|s| &mut s.rays);
+        for values in rays.filter_map(|r| r.data.get_mut(reflectivity)) {
+            values.iter_mut().for_each(|v| *v += delta);
+        }
+    }
+
+    if let Some(max_gap) = args.fill_gaps {
+        for sweep in &mut radar.sweeps {
+            rhix_converter::fill_gaps(sweep, max_gap);
+        }
+    }
+
+    if args.sort_azimuth {
+        radar
+            .sweeps
+            .iter_mut()
+            .for_each(rhix_converter::sort_azimuth);
+    }
+
+    if let Some(bins) = args.resample {
+        if header.geometry.observation_mode == rhix_converter::ObservationMode::Ppi {
+            for sweep in &mut radar.sweeps {
+                rhix_converter::resample_azimuth(sweep, bins);
+            }
+        } else {
+            log::warn!("Not resampling {}, it is not a PPI", path.display());
+        }
+    }
+
+    if args.stats {
+        match serde_json::to_string(&rhix_converter::moment_stats(radar)) {
+            Ok(stats) => println!(
+                "{} ({} samples per ray): {stats}",
+                radar.name, header.pulse.sample_num
+            ),
+            Err(e) => log::error!("Failed to serialize stats for {}: {e}", radar.name),
+        }
+    }
+
+    // Last, since every step above looks moments up by their own names
+    check_renames(&radar.params, &args.rename, &radar.name)?;
+    rename(&mut radar.params, &args.rename);
+    for ray in radar.sweeps.iter_mut().flat_map(|s| &mut s.rays) {
+        rename(&mut ray.data, &args.rename);
+    }
+
+    Ok(())
+}
+
+/// Checks --rename against the moments of a radar named `name`. A moment it does not record is
+/// warned about and left out, and a new name that another moment keeps is an error.
+fn check_renames<T>(
+    params: &HashMap<String, T>,
+    renames: &[(rhix_converter::Moment, String)],
+    name: &str,
+) -> Result<(), String> {
+    let mut sources = std::collections::HashSet::new();
+    let mut targets = std::collections::HashSet::new();
+    for (moment, target) in renames {
+        if !sources.insert(moment) {
+            return Err(format!("{moment} is renamed more than once"));
+        }
+        if !params.contains_key(moment.name()) {
+            log::warn!("Not renaming {moment} in {name}, it is not recorded");
+            continue;
+        }
+        let renamed_away = renames.iter().any(|(m, _)| m.name() == target);
+        if (params.contains_key(target) && !renamed_away) || !targets.insert(target) {
+            return Err(format!(
+                "Cannot rename {moment} to {target} in {name}, another moment is named {target}"
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies --rename to a map keyed by moment name. Every renamed entry is taken out before any
+/// is put back, so names can be swapped.
+fn rename<T>(data: &mut HashMap<String, T>, renames: &[(rhix_converter::Moment, String)]) {
+    let renamed: Vec<_> = renames
+        .iter()
+        .filter_map(|(moment, name)| data.remove(moment.name()).map(|v| (name.clone(), v)))
+        .collect();
+    data.extend(renamed);
+}
+
+/// Expands --name for an input named `stem`. Without a `header` the fields taken from it are
+/// left unexpanded.
+fn radar_name(stem: &str, header: Option<&rhix_converter::RhixHeader>, args: &Args) -> String {
+    let Some(template) = &args.name else {
+        return stem.to_string();
+    };
+
+    let name = template.replace("{stem}", stem);
+    match header {
+        Some(header) => name
+            .replace("{model}", &header.site.model_type.to_string())
+            .replace("{product}", &header.site.product_number.to_string()),
+        None => name,
+    }
+}
+
+/// Whether --name takes fields from the header, so it is only known once the header is read.
+fn name_uses_header(args: &Args) -> bool {
+    let template = args.name.as_deref().unwrap_or_default();
+    template.contains("{model}") || template.contains("{product}")
+}
+
+/// Extensions of the files next to silv output that are not written by silv: the --dump-header
+/// JSON and the outputs of the other formats.
+const NOT_SILV_EXTENSIONS: &[&str] = &["json", "csv", "nc", "h5"];
+
+/// Whether the output for a radar named `name` is already in the output directory.
+///
+/// silv picks its own file names, so any file named `<name>.<extension>` counts for it, unless
+/// it is the manifest or its extension is one of [`NOT_SILV_EXTENSIONS`].
+fn output_exists(name: &str, args: &Args) -> std::io::Result<bool> {
+    let output_dir = std::path::Path::new(&args.output_dir);
+    let extension = match args.output_format {
+        OutputFormat::Silv => {
+            let manifest = args.manifest.as_deref().and_then(|m| m.file_name());
+            for entry in std::fs::read_dir(output_dir)? {
+                let file_name = entry?.file_name();
+                if Some(&*file_name) != manifest
+                    && is_silv_output(name, &file_name.to_string_lossy())
+                {
+                    return Ok(true);
+                }
             }
-            Some("rhix") => bytes,
-            _ => panic!("Unknown file type"),
+            return Ok(false);
         }
+        OutputFormat::Csv => "csv",
+        #[cfg(feature = "cfradial")]
+        OutputFormat::Cfradial => "nc",
+        #[cfg(feature = "odim")]
+        OutputFormat::Odim => "h5",
     };
 
-    assert!(
-        readle!(data, u16) == 156,
-        "Header size is not 156, may have wrong format."
-    );
-    let _version = readle!(data, u16);
-    let start_time = (
-        readle!(data, u16),
-        readle!(data, u8),
-        readle!(data, u8),
-        readle!(data, u8),
-        readle!(data, u8),
-        readle!(data, u8),
-    );
-
-    readle!(data, u8);
-
-    let _end_time = (
-        readle!(data, u16),
-        readle!(data, u8),
-        readle!(data, u8),
-        readle!(data, u8),
-        readle!(data, u8),
-        readle!(data, u8),
-    );
-
-    readle!(data, u8);
-
-    let _timezone = readle!(data, i16);
-    let _productnumber = readle!(data, u16);
-    let _modeltype = readle!(data, u16);
-    let lat = readle!(data, i32) as f32 / 100000.0;
-    let lon = readle!(data, i32) as f32 / 100000.0;
-    let _alt = readle!(data, i32);
-    let _azi_offset = readle!(data, u16);
-    let _tx_freq = readle!(data, u32);
-    let _polarization = readle!(data, u16);
-    let _gain_h = readle!(data, u16);
-    let _gain_v = readle!(data, u16);
-    let _half_width_h = readle!(data, u16);
-    let _half_width_v = readle!(data, u16);
-    let _tx_power_h = readle!(data, u16);
-    let _tx_power_v = readle!(data, u16);
-    let _radar_const_h = readle!(data, i16);
-    let _radar_const_v = readle!(data, i16);
-    let _noise_power_h_short = readle!(data, i16);
-    let _noise_power_h_long = readle!(data, i16);
-    let _thresh_power_short = readle!(data, i16);
-    let _thresh_power_long = readle!(data, i16);
-    let _tx_pulse_spec = readle!(data, u16);
-    let _prf_mode = readle!(data, u16);
-    let _prf1 = readle!(data, u16);
-    let _prf2 = readle!(data, u16);
-    let _prf3 = readle!(data, u16);
-    let nyquist = readle!(data, u16) as f32 / 10.0;
-    let _sample_num = readle!(data, u16);
-    let _tx_pulse_blind_len = readle!(data, u16);
-    let _short_pulse_width = readle!(data, u16);
-    let _short_pulse_mod_bandwith = readle!(data, u16);
-    let _long_pulse_width = readle!(data, u16);
-    let _long_pulse_mod_bandwidth = readle!(data, u16);
-    let _pulse_switchpoint = readle!(data, u16);
-    let _observation_mode = readle!(data, u16);
-    let _rotation_speed = readle!(data, u16) as f32 / 10.0 / 60.0 * 360.0;
-    let _rays = readle!(data, u16);
-    let gates = readle!(data, u16);
-    let gate_res = readle!(data, u16);
-    let _scan_num = readle!(data, u16);
-    let _total_scans = readle!(data, u16);
-    let _rain_intensity_est = readle!(data, u16);
-    let _zr_coeff_b = readle!(data, u16);
-    let _zr_coeff_beta = readle!(data, u16);
-    let _kdp_coeff_a = readle!(data, u16);
-    let _kdp_coeff_b = readle!(data, u16);
-    let _kdp_coeff_c = readle!(data, u16);
-    let _zh_corr = readle!(data, u16);
-    let _zh_corr_b1 = readle!(data, u16);
-    let _zh_corr_b2 = readle!(data, u16);
-    let _zh_corr_d1 = readle!(data, u16);
-    let _zh_corr_d2 = readle!(data, u16);
-    let _air_attenuation = readle!(data, u16);
-    let _rain_thresh = readle!(data, u16);
-    let record_item = readle!(data, u16);
-    let (use_r, use_dbz, use_vel, use_zdr, use_kdp, use_phi, use_rho, use_w, use_quality) = (
-        record_item & 1,
-        record_item >> 1 & 1,
-        record_item >> 2 & 1,
-        record_item >> 3 & 1,
-        record_item >> 4 & 1,
-        record_item >> 5 & 1,
-        record_item >> 6 & 1,
-        record_item >> 7 & 1,
-        record_item >> 8 & 1,
-    );
-    let _signal_flag = readle!(data, u16);
-    let _clutter_ref_file = (
-        readle!(data, u16),
-        readle!(data, u8),
-        readle!(data, u8),
-        readle!(data, u8),
-        readle!(data, u8),
-        readle!(data, u8),
-    );
-    readle!(data, u8);
-    readle!(data, u64);
-
-    let mut radar = silv::RadarFile {
-        name: "FWLX".into(),
-        sweeps: Vec::new(),
-        params: std::collections::HashMap::new(),
+    let mut file_name = format!("{name}.{extension}");
+    if let Some(compressed) = args.compress.extension() {
+        file_name = format!("{file_name}.{compressed}");
+    }
+    Ok(output_dir.join(file_name).exists())
+}
+
+/// Whether `file_name` in the output directory may be silv's output for a radar named `name`.
+fn is_silv_output(name: &str, file_name: &str) -> bool {
+    let Some(extensions) = file_name
+        .strip_prefix(name)
+        .and_then(|s| s.strip_prefix('.'))
+    else {
+        return false;
     };
+    // Compressed side files keep their own extension first, as in `<name>.json.gz`
+    let extension = extensions.split('.').next().unwrap_or_default();
+    !NOT_SILV_EXTENSIONS.contains(&extension)
+}
+
+/// Whether to leave out a scan named `name` under --if-exists, failing it with `error`.
+fn skip_existing(name: &str, args: &Args) -> Result<bool, Box<dyn std::error::Error>> {
+    let replaced = matches!(args.if_exists, IfExists::Overwrite | IfExists::Append);
+    if replaced || args.dry_run || args.merge_volume {
+        return Ok(false);
+    }
+
+    let mut name = name.to_string();
+    if args.flatten {
+        // The first sweep stands in for the rest
+        name.push_str("_00");
+    }
+
+    if !output_exists(&name, args)? {
+        return Ok(false);
+    }
+    match args.if_exists {
+        IfExists::Error => Err(format!("Output {name} already exists").into()),
+        _ => Ok(true),
+    }
+}
+
+/// Writes a file through `write` to a hidden temporary file next to `path`, then renames it into
+/// place so other readers never see it half written.
+fn write_atomic(
+    path: &std::path::Path,
+    write: impl FnOnce(&std::path::Path) -> Result<(), Box<dyn std::error::Error>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = path.with_file_name(format!(".{file_name}.tmp"));
 
-    let all_data_types = [
-        (use_r, "R"),
-        (use_dbz, "REF"),
-        (use_vel, "VEL"),
-        (use_zdr, "ZDR"),
-        (use_kdp, "KDP"),
-        (use_phi, "PHI"),
-        (use_rho, "RHO"),
-        (use_w, "SW"),
-        (use_quality, "")
-    ];
-
-    for (data_type, name) in all_data_types {
-        if data_type != 0 && name != "" {
-            radar.params.insert(
-                name.into(),
-                silv::ParamDescription {
-                    description: String::new(),
-                    units: String::new(),
-                    meters_to_first_cell: 0.0,
-                    meters_between_cells: gate_res as f32,
+    let result = write(&tmp).and_then(|()| Ok(std::fs::rename(&tmp, path)?));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+
+    result
+}
+
+/// Copies `src` into a new file at `dst` through the encoder for `compress`.
+fn compress_file(
+    src: &std::path::Path,
+    dst: &std::path::Path,
+    compress: Compress,
+) -> std::io::Result<()> {
+    let mut input = std::fs::File::open(src)?;
+    let output = std::fs::File::create(dst)?;
+    match compress {
+        Compress::None => {
+            std::io::copy(&mut input, &mut { output })?;
+        }
+        Compress::Gz => {
+            let mut encoder = flate2::write::GzEncoder::new(output, flate2::Compression::default());
+            std::io::copy(&mut input, &mut encoder)?;
+            encoder.finish()?;
+        }
+        Compress::Zst => {
+            let mut encoder = zstd::stream::write::Encoder::new(output, 0)?;
+            std::io::copy(&mut input, &mut encoder)?;
+            encoder.finish()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes an output file through `write` like `write_atomic`, returning where it was written.
+/// With --compress the file is written uncompressed first and then compressed into `path` with
+/// the extension appended.
+fn write_output(
+    path: &std::path::Path,
+    args: &Args,
+    write: impl FnOnce(&std::path::Path) -> Result<(), Box<dyn std::error::Error>>,
+) -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
+    let Some(extension) = args.compress.extension() else {
+        return write_atomic(path, write).map(|()| path.to_path_buf());
+    };
+
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let raw = path.with_file_name(format!(".{file_name}.raw"));
+    let compressed = path.with_file_name(format!("{file_name}.{extension}"));
+    let result = write(&raw).and_then(|()| {
+        write_atomic(&compressed, |tmp| {
+            Ok(compress_file(&raw, tmp, args.compress)?)
+        })
+    });
+    let _ = std::fs::remove_file(&raw);
+
+    result.map(|()| compressed)
+}
+
+/// Writes a radar file, and its header with --dump-header, into the output directory and
+/// returns the paths written. With --flatten every sweep is written separately.
+fn write(
+    header: &rhix_converter::RhixHeader,
+    radar: silv::RadarFile,
+    args: &Args,
+    options: &rhix_converter::ReadOptions,
+) -> Result<Vec<std::path::PathBuf>, Box<dyn std::error::Error>> {
+    if args.flatten {
+        let mut outputs = Vec::new();
+        for sweep in rhix_converter::split_sweeps(radar) {
+            outputs.extend(write_one(header, sweep, args, options)?);
+        }
+        return Ok(outputs);
+    }
+
+    write_one(header, radar, args, options)
+}
+
+fn write_one(
+    header: &rhix_converter::RhixHeader,
+    radar: silv::RadarFile,
+    args: &Args,
+    #[cfg_attr(not(feature = "odim"), allow(unused_variables))]
+    options: &rhix_converter::ReadOptions,
+) -> Result<Vec<std::path::PathBuf>, Box<dyn std::error::Error>> {
+    let output_dir = std::path::Path::new(&args.output_dir);
+    let mut outputs = Vec::new();
+
+    let appending = args.if_exists == IfExists::Append && output_exists(&radar.name, args)?;
+    if appending && args.output_format != OutputFormat::Csv {
+        let name = &radar.name;
+        return Err(format!("Cannot append to {name}, only CSV output can be read back").into());
+    }
+
+    if args.dump_header {
+        let path = output_dir.join(format!("{}.json", radar.name));
+        write_atomic(&path, |tmp| {
+            let file = std::fs::File::create(tmp)?;
+            match args.compact {
+                true => serde_json::to_writer(file, header)?,
+                false => serde_json::to_writer_pretty(file, header)?,
+            }
+            Ok(())
+        })?;
+        outputs.push(path);
+    }
+
+    match args.output_format {
+        OutputFormat::Silv => {
+            // silv picks its own file names, so it writes into a temporary directory on the
+            // same filesystem and each file it produced is renamed into place
+            let tmp = output_dir.join(format!(".{}.tmp", radar.name));
+            std::fs::create_dir_all(&tmp)?;
+            let tmp_dir = tmp.to_string_lossy().into_owned();
+            let name = radar.name.clone();
+            write_silv(radar, &tmp_dir).inspect_err(|_| {
+                let _ = std::fs::remove_dir_all(&tmp);
+            })?;
+            if std::fs::read_dir(&tmp)?.next().is_none() {
+                std::fs::remove_dir(&tmp)?;
+                return Err(format!("silv wrote no files for {name}").into());
+            }
+            for entry in std::fs::read_dir(&tmp)? {
+                let entry = entry?;
+                let path = output_dir.join(entry.file_name());
+                match args.compress.extension() {
+                    Some(extension) => {
+                        let mut file_name = path.into_os_string();
+                        file_name.push(format!(".{extension}"));
+                        let path = std::path::PathBuf::from(file_name);
+                        write_atomic(&path, |tmp| {
+                            Ok(compress_file(&entry.path(), tmp, args.compress)?)
+                        })?;
+                        std::fs::remove_file(entry.path())?;
+                        outputs.push(path);
+                    }
+                    None => {
+                        std::fs::rename(entry.path(), &path)?;
+                        outputs.push(path);
+                    }
+                }
+            }
+            std::fs::remove_dir(&tmp)?;
+        }
+        OutputFormat::Csv if appending => {
+            let path = output_dir.join(format!("{}.csv", radar.name));
+            append_csv(&path, radar, header, args)?;
+            outputs.push(path);
+        }
+        OutputFormat::Csv => {
+            let path = write_output(
+                &output_dir.join(format!("{}.csv", radar.name)),
+                args,
+                |tmp| {
+                    Ok(rhix_converter::write_csv(
+                        &radar,
+                        std::fs::File::create(tmp)?,
+                        args.csv_locations.then_some(header.site.altitude as f32),
+                        args.fill_value,
+                    )?)
+                },
+            )?;
+            outputs.push(path);
+        }
+        #[cfg(feature = "cfradial")]
+        OutputFormat::Cfradial => {
+            let path = write_output(
+                &output_dir.join(format!("{}.nc", radar.name)),
+                args,
+                |tmp| {
+                    Ok(rhix_converter::write_cfradial(
+                        &radar,
+                        header,
+                        args.fill_value,
+                        tmp,
+                    )?)
                 },
+            )?;
+            outputs.push(path);
+        }
+        #[cfg(feature = "odim")]
+        OutputFormat::Odim => {
+            let path = write_output(
+                &output_dir.join(format!("{}.h5", radar.name)),
+                args,
+                |tmp| {
+                    Ok(rhix_converter::write_odim(
+                        &radar,
+                        header,
+                        &options.calibration,
+                        tmp,
+                    )?)
+                },
+            )?;
+            outputs.push(path);
+        }
+    }
+
+    Ok(outputs)
+}
+
+/// Adds the sweeps of `radar` to the CSV output at `path` for --if-exists append. Sweeps at an
+/// elevation the file already has rows for are left out.
+fn append_csv(
+    path: &std::path::Path,
+    mut radar: silv::RadarFile,
+    header: &rhix_converter::RhixHeader,
+    args: &Args,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let existing = std::fs::read_to_string(path)?;
+    let mut elevations: Vec<f32> = existing
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split(',').nth(1)?.parse().ok())
+        .collect();
+    elevations.sort_by(f32::total_cmp);
+    elevations.dedup();
+
+    radar.sweeps.retain(|sweep| {
+        let distance = |e: &f32| (e - sweep.elevation).abs();
+        let duplicate = elevations
+            .iter()
+            .any(|e| distance(e) <= APPEND_ELEVATION_TOLERANCE);
+        if duplicate {
+            log::warn!(
+                "Not appending the {:.2} degree sweep to {}, it already has one",
+                sweep.elevation,
+                path.display()
             );
         }
+        !duplicate
+    });
+    if radar.sweeps.is_empty() {
+        return Ok(());
     }
 
-    let mut sweep = silv::Sweep {
-        latitude: lat,
-        longitude: lon,
-        elevation: 0.0,
-        nyquist_velocity: nyquist,
-        ..Default::default()
-    };
+    let mut rows = Vec::new();
+    let locations = args.csv_locations.then_some(header.site.altitude as f32);
+    rhix_converter::write_csv(&radar, &mut rows, locations, args.fill_value)?;
+    let rows = String::from_utf8(rows)?;
+    let (columns, rows) = rows.split_once('\n').unwrap_or_default();
+    if existing.lines().next() != Some(columns) {
+        return Err(format!("Cannot append to {}, its columns differ", path.display()).into());
+    }
+
+    write_atomic(path, |tmp| Ok(std::fs::write(tmp, existing + rows)?))
+}
 
-    while !data.is_empty() {
-        let size = readle!(data, u16);
-        if size != 6 {
-            panic!("Angle information block size error, found {size}");
-        }
-
-        let _azimuth = readle!(data, u16) as f32 / 100.0;
-        let elevation = readle!(data, u16) as f32 / 100.0;
-
-        let mut ray = silv::Ray {
-            azimuth: -elevation + 90.0,
-            time: chrono::Utc.with_ymd_and_hms(
-                start_time.0 as i32,
-                start_time.1 as u32,
-                start_time.2 as u32,
-                start_time.3 as u32,
-                start_time.4 as u32,
-                start_time.5 as u32,
-            ).latest().unwrap(),
-            data: std::collections::HashMap::default(),
+/// Calls `silv::write`, which reports nothing back, turning a panic inside it into an error.
+fn write_silv(radar: silv::RadarFile, dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let name = radar.name.clone();
+    // silv is an unpinned git dependency and its RadyOptions fields have not been checked against
+    // a specific revision, so none are exposed as flags yet. Pin a rev in Cargo.toml, confirm the
+    // fields it offers, and pass them through from Args here.
+    let write = || silv::write(radar, dir, &silv::RadyOptions::default());
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(write)).map_err(|panic| {
+        let message = match panic.downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => panic.downcast_ref::<String>().cloned().unwrap_or_default(),
         };
+        format!("silv panicked writing {name}: {message}").into()
+    })
+}
 
-        let observed_block_size = readle!(data, u16);
+fn convert(
+    path: &std::path::Path,
+    args: &Args,
+    options: &rhix_converter::ReadOptions,
+) -> Result<Outcome, Failure> {
+    let read_failure = |e: Box<dyn std::error::Error>| Failure::Read(e.to_string());
+    // Checked before reading, with the name a gzip stored filename gives the scan. Archives are
+    // checked per entry while reading.
+    if path.as_os_str() != "-" && !is_zip(path) {
+        // Reading the header for the name only matters when an existing output is not
+        // overwritten anyway
+        let checked = matches!(args.if_exists, IfExists::Skip | IfExists::Error);
+        let header = match name_uses_header(args) && checked {
+            true => Some(
+                rhix_converter::read_header(path, args.endian)
+                    .map_err(|e| Failure::Read(e.to_string()))?,
+            ),
+            false => None,
+        };
+        let stem = rhix_converter::scan_name(path).map_err(|e| Failure::Read(e.to_string()))?;
+        let name = radar_name(&stem, header.as_ref(), args);
+        if skip_existing(&name, args).map_err(read_failure)? {
+            return Ok(Outcome::Exists);
+        }
+    }
+
+    let (scans, existing) = load(path, args, options).map_err(read_failure)?;
+    if scans.is_empty() {
+        return Ok(nothing_loaded(existing));
+    }
 
-        assert!(
-            (observed_block_size - 2)
-                / (use_r + use_dbz + use_vel + use_zdr + use_kdp + use_phi + use_rho + use_w + use_quality)
-                / 2
-                == gates,
-            "Observed block error",
+    let mut written = Vec::new();
+    for (header, radar) in scans {
+        let mut scan = Written::new(&header, &radar);
+        if !args.dry_run {
+            scan.outputs =
+                write(&header, radar, args, options).map_err(|e| Failure::Write(e.to_string()))?;
+            log::info!("Converted {} to {}", path.display(), scan.name);
+        }
+        written.push(scan);
+    }
+
+    Ok(Outcome::Converted(written))
+}
+
+/// Furthest a sweep's elevation may be from --select-elevation, in degrees.
+const SELECT_ELEVATION_TOLERANCE: f32 = 0.2;
+
+/// Elevation difference in degrees within which a sweep added by --if-exists append counts as
+/// one the output already has.
+const APPEND_ELEVATION_TOLERANCE: f32 = 0.05;
+
+/// Set by the first Ctrl-C, after which no new file is started.
+static INTERRUPTED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Exit code of an interrupted run, the shell convention for SIGINT.
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Longest time between consecutive scans of one volume for --merge-volume.
+const MAX_VOLUME_GAP_SECONDS: i64 = 10 * 60;
+
+/// Largest --time-offset, a leap year. Clock drift is far smaller, and larger offsets could
+/// move times out of the range a timestamp holds.
+const MAX_TIME_OFFSET_SECONDS: f64 = 366.0 * 24.0 * 60.0 * 60.0;
+
+fn main() {
+    let mut args = Args::parse();
+    if args.verify {
+        args.dry_run = true;
+        args.strict = true;
+    }
+
+    let level = match args.verbose {
+        0 => log::LevelFilter::Warn,
+        1 => log::LevelFilter::Info,
+        2 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
+    };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_default_env()
+        .init();
+
+    // Files in progress finish, and their atomic writes mean an interrupted write leaves nothing
+    // behind. A second Ctrl-C stops at once.
+    let handler = ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, std::sync::atomic::Ordering::SeqCst) {
+            std::process::exit(INTERRUPTED_EXIT_CODE);
+        }
+        eprintln!("Interrupted, finishing the files in progress. Press Ctrl-C again to stop now.");
+    });
+    if let Err(e) = handler {
+        log::warn!("Failed to install the Ctrl-C handler: {e}");
+    }
+
+    for extension in &args.extensions {
+        if extension != "zip" && rhix_converter::InputFormat::from_extension(extension).is_none() {
+            eprintln!("Unsupported input extension [{extension}]");
+            std::process::exit(1);
+        }
+    }
+
+    if args.if_exists == IfExists::Append && args.compress != Compress::None {
+        eprintln!("--if-exists append cannot add to compressed outputs");
+        std::process::exit(1);
+    }
+
+    // ODIM gain and offset already carry the scaling, raw counts would be scaled twice
+    #[cfg(feature = "odim")]
+    if args.raw && args.output_format == OutputFormat::Odim {
+        eprintln!("--raw cannot be written as ODIM, which stores raw counts already");
+        std::process::exit(1);
+    }
+
+    if !args.dry_run && !args.list && !args.coverage {
+        if let Err(e) = std::fs::create_dir_all(&args.output_dir) {
+            eprintln!("Failed to create {}: {e}", args.output_dir);
+            std::process::exit(1);
+        }
+    }
+
+    let calibration = match &args.calibration {
+        Some(path) => read_calibration(path).unwrap_or_else(|e| {
+            eprintln!("Failed to read calibration {}: {e}", path.display());
+            std::process::exit(1);
+        }),
+        None => HashMap::new(),
+    };
+
+    let options = rhix_converter::ReadOptions {
+        sweep_threshold: args.sweep_threshold,
+        elevation_tolerance: args.elevation_tolerance,
+        quality: !args.no_quality,
+        moments: (!args.moments.is_empty()).then(|| args.moments.clone()),
+        no_data: args.no_data.iter().copied().collect(),
+        raw: args.raw,
+        calibration,
+        azimuth_offset: args.apply_azi_offset,
+        azimuth_convention: args.azimuth_convention,
+        velocity_sign: args.velocity_sign,
+        strict: args.strict,
+        endian: args.endian,
+    };
+
+    if let Some(jobs) = args.jobs {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build_global()
+            .unwrap();
+    }
+
+    let mut paths = Vec::new();
+    if args.files == "-" {
+        paths.push(std::path::PathBuf::from("-"));
+    } else if args.recursive {
+        for entry in walkdir::WalkDir::new(&args.files).sort_by_file_name() {
+            match entry {
+                Ok(entry) if entry.file_type().is_file() => paths.push(entry.into_path()),
+                Ok(_) => {}
+                Err(e) => log::error!("Failed to walk {}: {e}", args.files),
+            }
+        }
+    } else {
+        for file in glob::glob(&args.files).unwrap() {
+            match file {
+                Ok(path) => paths.push(path),
+                Err(e) => log::error!("Failed to read {}: {}", e.path().display(), e.error()),
+            }
+        }
+    }
+
+    let (mut paths, ignored): (Vec<_>, Vec<_>) = paths.into_iter().partition(|path| {
+        let extension = path.extension().unwrap_or_default().to_string_lossy();
+        path.as_os_str() == "-" || args.extensions.iter().any(|ex| *ex == extension)
+    });
+    for path in &ignored {
+        log::warn!(
+            "Skipping {}, its extension is not in --extensions",
+            path.display()
         );
+    }
+
+    // Sorted explicitly, so runs and the --limit sample are the same on every platform
+    paths.sort();
+    if args.sort_by == SortBy::Time {
+        paths.sort_by_cached_key(|path| {
+            let header = rhix_converter::read_header(path, args.endian).ok();
+            let start = header.and_then(|h| h.timing.start_time.to_utc(h.utc_offset().ok()?).ok());
+            (start.is_none(), start)
+        });
+    }
+
+    let limited = args
+        .limit
+        .filter(|&limit| paths.len() > limit)
+        .map(|limit| {
+            let total = paths.len();
+            paths.truncate(limit);
+            total
+        });
+
+    let progress = if args.quiet || args.stats || !std::io::stdout().is_terminal() {
+        indicatif::ProgressBar::hidden()
+    } else {
+        indicatif::ProgressBar::new(paths.len() as u64).with_style(
+            indicatif::ProgressStyle::with_template("{bar:40} {pos}/{len} {msg}").unwrap(),
+        )
+    };
+
+    let process = |path: &std::path::Path| {
+        if INTERRUPTED.load(std::sync::atomic::Ordering::SeqCst) {
+            return Ok(Outcome::Interrupted);
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        progress.set_message(name.into_owned());
+        let result = if args.list || args.coverage {
+            list(path, &args, &options).map_err(|e| Failure::Read(e.to_string()))
+        } else if args.merge_volume {
+            load(path, &args, &options)
+                .map(|(loaded, existing)| match loaded.is_empty() {
+                    true => nothing_loaded(existing),
+                    false => Outcome::Loaded(loaded),
+                })
+                .map_err(|e| Failure::Read(e.to_string()))
+        } else {
+            convert(path, &args, &options)
+        };
+        progress.inc(1);
+        result
+    };
+
+    // Results are collected so they are reported together once every file is done
+    let loaded: Vec<_> = paths.par_iter().map(|path| (path, process(path))).collect();
+    progress.finish_and_clear();
 
-        for (data_type, name) in all_data_types {
-            let data = readle!(data, u16, gates as usize);
-            if data_type != 0 && name != "" {
-                let data = data
-                    .into_iter()
-                    .map(|v| read_data(v, name))
-                    .collect();
+    let mut scans = Vec::new();
+    // Where each held back scan is in the results, to list its volume's outputs
+    let mut origins: HashMap<String, (usize, usize)> = HashMap::new();
+    let mut results: Vec<_> = loaded
+        .into_iter()
+        .enumerate()
+        .map(|(i, (path, result))| {
+            // Merged scans are written per volume below, so each file only reports its parse
+            let result = result.and_then(|outcome| match outcome {
+                Outcome::Loaded(loaded) => {
+                    // Volumes are named after a scan, so same-named scans, such as one day's
+                    // file under another day's directory, would share an output
+                    let duplicate = loaded
+                        .iter()
+                        .find_map(|(_, radar)| Some((&radar.name, origins.get(&radar.name)?)));
+                    if let Some((name, &(first, _))) = duplicate {
+                        return Err(Failure::Read(format!(
+                            "scan {name} is also read from {}, rename one to merge both",
+                            paths[first].display()
+                        )));
+                    }
+                    let mut written = Vec::new();
+                    for (j, (header, radar)) in loaded.into_iter().enumerate() {
+                        origins.insert(radar.name.clone(), (i, j));
+                        written.push(Written::new(&header, &radar));
+                        scans.push((header, radar));
+                    }
+                    Ok(Outcome::Converted(written))
+                }
+                outcome => Ok(outcome),
+            });
+            (path, result)
+        })
+        .collect();
 
-                ray.data.insert(name.into(), data);
+    let interrupted = INTERRUPTED.load(std::sync::atomic::Ordering::SeqCst);
+    if args.merge_volume && !args.dry_run && !args.list && !args.coverage && !interrupted {
+        let max_gap = chrono::Duration::seconds(MAX_VOLUME_GAP_SECONDS);
+        for (header, radar) in rhix_converter::merge_volumes(scans, max_gap) {
+            let name = radar.name.clone();
+            match write(&header, radar, &args, &options) {
+                Ok(outputs) => {
+                    log::info!("Wrote volume {name}");
+                    let (i, j) = origins[&name];
+                    if let (_, Ok(Outcome::Converted(written))) = &mut results[i] {
+                        written[j].outputs = outputs;
+                    }
+                }
+                Err(e) => {
+                    // Reported against the file of its first scan
+                    let (i, _) = origins[&name];
+                    results[i].1 = Err(Failure::Write(format!("volume {name}: {e}")));
+                }
             }
         }
+    }
 
-        sweep.rays.push(ray);
+    for (path, result) in &results {
+        match result {
+            Ok(Outcome::Skipped) => {
+                log::debug!("Skipped {}, outside the time window", path.display())
+            }
+            Ok(Outcome::Exists) => {
+                log::debug!("Skipped {}, its output already exists", path.display())
+            }
+            Ok(Outcome::Listed(lines)) => {
+                for (label, line) in lines {
+                    println!("{label}: {line}");
+                }
+            }
+            Ok(Outcome::Headers(_)) => {}
+            Ok(_) if args.dry_run => println!("{}: OK", path.display()),
+            Ok(_) => {}
+            Err(e) if args.verify => println!("{}: FAILED: {e}", path.display()),
+            Err(Failure::Read(e)) => log::error!("Failed to convert {}: {e}", path.display()),
+            Err(Failure::Write(e)) => log::error!("Failed to write {}: {e}", path.display()),
+        }
     }
 
-    radar.sweeps.push(sweep);
+    if args.coverage {
+        let headers: Vec<_> = results
+            .iter()
+            .flat_map(|(_, result)| match result {
+                Ok(Outcome::Headers(headers)) => &headers[..],
+                _ => &[],
+            })
+            .collect();
+        print_coverage(&headers);
+    }
 
-    silv::write(radar, ".", &silv::RadyOptions::default());
-}
+    let skipped = results
+        .iter()
+        .filter(|(_, result)| matches!(result, Ok(Outcome::Skipped)))
+        .count();
+    if skipped > 0 {
+        println!("{skipped} files skipped by the time filter");
+    }
 
-#[derive(Parser)]
-struct Args {
-    /// Path(s) of file to convert. For a folder, use a * symbol at the end.
-    #[clap(short, long, value_parser)]
-    files: String,
+    let existing = results
+        .iter()
+        .filter(|(_, result)| matches!(result, Ok(Outcome::Exists)))
+        .count();
+    if existing > 0 {
+        println!("{existing} files skipped as their output already exists");
+    }
+
+    let write_failures = results
+        .iter()
+        .filter(|(_, result)| matches!(result, Err(Failure::Write(_))))
+        .count();
+    let read_failures = results.iter().filter(|(_, r)| r.is_err()).count() - write_failures;
+    if read_failures > 0 && !args.dry_run {
+        println!("{read_failures} files failed to convert");
+    }
+    if write_failures > 0 {
+        println!("{write_failures} files failed to write");
+    }
+
+    if !ignored.is_empty() {
+        println!("{} files skipped by their extension", ignored.len());
+    }
+
+    if let Some(total) = limited {
+        println!("Stopped at the limit of {} of {total} files", results.len());
+    }
+
+    let not_started = results
+        .iter()
+        .filter(|(_, result)| matches!(result, Ok(Outcome::Interrupted)))
+        .count();
+    if interrupted {
+        let done = results.len() - not_started;
+        println!("Interrupted after {done} of {} files", results.len());
+    }
+
+    if let Some(manifest) = &args.manifest {
+        let entries: Vec<_> = results
+            .iter()
+            .map(|(path, result)| {
+                let (status, error, scans) = match result {
+                    Ok(Outcome::Converted(written)) => ("ok", None, &written[..]),
+                    Ok(Outcome::Skipped) => ("skipped", None, &[][..]),
+                    Ok(Outcome::Exists) => ("exists", None, &[][..]),
+                    Ok(Outcome::Interrupted) => ("interrupted", None, &[][..]),
+                    Ok(_) => ("ok", None, &[][..]),
+                    Err(e @ Failure::Read(_)) => ("error", Some(e.message()), &[][..]),
+                    Err(e @ Failure::Write(_)) => ("write_error", Some(e.message()), &[][..]),
+                };
+                ManifestEntry {
+                    input: path,
+                    status,
+                    error,
+                    scans,
+                }
+            })
+            .collect();
+
+        let written = write_atomic(manifest, |tmp| {
+            Ok(serde_json::to_writer_pretty(
+                std::fs::File::create(tmp)?,
+                &entries,
+            )?)
+        });
+        if let Err(e) = written {
+            log::error!("Failed to write manifest {}: {e}", manifest.display());
+        }
+    }
+
+    if args.dry_run {
+        let parsed = results
+            .iter()
+            .filter(|(_, result)| matches!(result, Ok(Outcome::Converted(_))))
+            .count();
+        let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+        if args.verify {
+            println!("{parsed} passed, {failed} failed");
+        } else {
+            println!(
+                "{parsed} of {} files parsed cleanly",
+                results.len() - skipped
+            );
+        }
+
+        if args.verify && failed > 0 && !interrupted {
+            std::process::exit(1);
+        }
+    }
+
+    if interrupted {
+        std::process::exit(INTERRUPTED_EXIT_CODE);
+    }
 }
 
-fn main() {
-    let args = Args::parse();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_offsets_parse_within_a_year() {
+        let offset = |s: &str| parse_time_offset(s).map(|d| d.num_milliseconds());
+        assert_eq!(offset("1.5"), Ok(1500));
+        assert_eq!(offset("-86400"), Ok(-86_400_000));
+        for invalid in ["-1e300", "1e13", "31622401", "nan", "inf", "soon"] {
+            assert!(offset(invalid).is_err(), "{invalid}");
+        }
+    }
 
-    for file in glob::glob(&args.files).unwrap() {
-        read_file(file.unwrap())
+    #[test]
+    fn renames_keep_names_unique() {
+        use rhix_converter::Moment::{Reflectivity, SpectrumWidth, Velocity};
+        let params: HashMap<_, _> = [("REF".to_string(), ()), ("VEL".to_string(), ())].into();
+        let check = |renames: &[(rhix_converter::Moment, &str)]| {
+            let renames: Vec<_> = renames.iter().map(|&(m, n)| (m, n.to_string())).collect();
+            check_renames(&params, &renames, "scan")
+        };
+
+        assert!(check(&[(Reflectivity, "DBZH"), (Velocity, "VRADH")]).is_ok());
+        assert!(check(&[(Reflectivity, "VEL"), (Velocity, "REF")]).is_ok());
+        // Not recorded, only warned about
+        assert!(check(&[(SpectrumWidth, "REF")]).is_ok());
+
+        assert!(check(&[(Reflectivity, "VEL")]).is_err());
+        assert!(check(&[(Reflectivity, "X"), (Velocity, "X")]).is_err());
+        assert!(check(&[(Reflectivity, "X"), (Reflectivity, "Y")]).is_err());
+    }
+
+    #[test]
+    fn side_files_are_not_silv_output() {
+        assert!(is_silv_output("scan", "scan.bin"));
+        assert!(is_silv_output("scan", "scan.bin.gz"));
+        for other in [
+            "scan.json",
+            "scan.json.gz",
+            "scan.csv",
+            "scan.nc",
+            "scan.h5",
+            "scan_00.bin",
+        ] {
+            assert!(!is_silv_output("scan", other), "{other}");
+        }
+        assert!(!is_silv_output("scan", "scanner.bin"));
     }
 }
@@ -0,0 +1,245 @@
+use serde::Deserialize;
+
+/// One of the moments the format can record, in record_item bit order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Moment {
+    RainRate,
+    Reflectivity,
+    Velocity,
+    Zdr,
+    Kdp,
+    Phi,
+    Rho,
+    SpectrumWidth,
+    Quality,
+}
+
+impl Moment {
+    /// Every moment, in record_item bit order.
+    pub const ALL: [Moment; 9] = [
+        Moment::RainRate,
+        Moment::Reflectivity,
+        Moment::Velocity,
+        Moment::Zdr,
+        Moment::Kdp,
+        Moment::Phi,
+        Moment::Rho,
+        Moment::SpectrumWidth,
+        Moment::Quality,
+    ];
+
+    /// The moments that need a vertical channel, so only a dual polarization scan measures them.
+    pub const DUAL_POL: [Moment; 4] = [Moment::Zdr, Moment::Kdp, Moment::Phi, Moment::Rho];
+
+    /// Position of the moment's flag in record_item.
+    pub fn bit(self) -> usize {
+        self as usize
+    }
+
+    pub fn info(self) -> &'static MomentInfo {
+        &MOMENTS[self.bit()]
+    }
+
+    /// Short name, used as the key of the moment's data.
+    pub fn name(self) -> &'static str {
+        self.info().name
+    }
+
+    pub fn units(self) -> &'static str {
+        self.info().units
+    }
+
+    pub fn description(self) -> &'static str {
+        self.info().description
+    }
+
+    /// Looks up a moment by its short name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Moment::ALL.into_iter().find(|m| m.name() == name)
+    }
+
+    /// The built-in scaling from [`MOMENTS`].
+    pub fn calibration(self) -> Calibration {
+        let info = self.info();
+        Calibration {
+            offset: info.offset,
+            scale: info.scale,
+        }
+    }
+
+    /// Decodes a raw value into physical units, or NaN for the moment's no-data code.
+    pub fn decode(self, raw: u16) -> f64 {
+        self.clamp(self.calibration().decode(raw, self.info().no_data))
+    }
+
+    /// Raises a decoded value to the moment's physical minimum.
+    ///
+    /// The SW scaling of `(raw - 1) * 0.01` puts raw 1 at a width of 0. Raw 0 is its no-data
+    /// code, but decodes to -0.01 m/s once that code is disabled, and a calibration can shift
+    /// values below 0 too. Spectrum width cannot be negative, so such values become 0
+    /// rather than missing, as they are the narrowest width measured. NaN is kept.
+    pub fn clamp(self, value: f64) -> f64 {
+        match self {
+            Moment::SpectrumWidth if value < 0.0 => 0.0,
+            _ => value,
+        }
+    }
+}
+
+/// Linear scaling of raw counts, applied as `(raw - offset) * scale`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Calibration {
+    pub offset: f64,
+    pub scale: f64,
+}
+
+impl Calibration {
+    /// Scales a raw value into physical units, or NaN when it is the `no_data` code.
+    pub fn decode(self, raw: u16, no_data: Option<u16>) -> f64 {
+        if no_data == Some(raw) {
+            return f64::NAN;
+        }
+
+        (raw as f64 - self.offset) * self.scale
+    }
+}
+
+/// Which way positive radial velocities point in the output.
+///
+/// WR2120 files record velocities as positive away from the radar, the convention kept by
+/// default and the one CfRadial and ODIM expect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VelocitySign {
+    /// Positive velocities move away from the radar.
+    Away,
+    /// Positive velocities move toward the radar.
+    Toward,
+}
+
+impl std::str::FromStr for VelocitySign {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "away" => Ok(VelocitySign::Away),
+            "toward" => Ok(VelocitySign::Toward),
+            _ => Err(format!(
+                "Unknown velocity sign [{s}], expected away or toward"
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Moment {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for Moment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Moment::from_name(s).ok_or_else(|| format!("Unknown moment [{s}]"))
+    }
+}
+
+/// Naming and scaling of one recorded moment.
+#[derive(Debug, Clone, Copy)]
+pub struct MomentInfo {
+    pub name: &'static str,
+    pub units: &'static str,
+    pub description: &'static str,
+    /// Raw value that maps to zero.
+    pub offset: f64,
+    /// Physical units per raw count.
+    pub scale: f64,
+    /// Raw value marking a gate without an echo, decoded as NaN.
+    pub no_data: Option<u16>,
+}
+
+/// All moments the format can record, in record_item bit order.
+///
+/// Raw 0 is the no-echo code. It sits far below any real value of the moments offset by 32768,
+/// and RHO and SW start counting at 1 to leave it free.
+#[rustfmt::skip]
+pub const MOMENTS: [MomentInfo; 9] = [
+    // Rain rate is assumed to be stored like reflectivity, as (N - 32768) / 100 but in mm/h.
+    // This is not confirmed by the format documentation, override it with --calibration if a
+    // file shows otherwise. Values just below zero are kept as recorded rather than clamped.
+    MomentInfo { name: "R", units: "mm/h", description: "Rain Rate", offset: 32768.0, scale: 0.01, no_data: Some(0) },
+    MomentInfo { name: "REF", units: "dBZ", description: "Reflectivity", offset: 32768.0, scale: 0.01, no_data: Some(0) },
+    MomentInfo { name: "VEL", units: "m/s", description: "Radial Velocity", offset: 32768.0, scale: 0.01, no_data: Some(0) },
+    MomentInfo { name: "ZDR", units: "dB", description: "Differential Reflectivity", offset: 32768.0, scale: 0.01, no_data: Some(0) },
+    MomentInfo { name: "KDP", units: "deg/km", description: "Specific Differential Phase", offset: 32768.0, scale: 0.01, no_data: Some(0) },
+    MomentInfo { name: "PHI", units: "deg", description: "Differential Phase", offset: 32768.0, scale: 360.0 / 65535.0, no_data: Some(0) },
+    MomentInfo { name: "RHO", units: "", description: "Correlation Coefficient", offset: 1.0, scale: 2.0 / 65534.0, no_data: Some(0) },
+    MomentInfo { name: "SW", units: "m/s", description: "Spectrum Width", offset: 1.0, scale: 0.01, no_data: Some(0) },
+    // Scaling is not documented, so quality is kept as the raw value
+    MomentInfo { name: "QC", units: "raw", description: "Quality", offset: 0.0, scale: 1.0, no_data: None },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rain_rate_decodes_to_mm_per_hour() {
+        assert_eq!(Moment::RainRate.units(), "mm/h");
+        assert_eq!(Moment::RainRate.decode(32768), 0.0);
+        assert_eq!(Moment::RainRate.decode(32768 + 1250), 12.5);
+        assert!(Moment::RainRate.decode(0).is_nan());
+    }
+
+    #[test]
+    fn every_moment_decodes_to_physical_units() {
+        let pairs = [
+            (Moment::RainRate, 32768 + 1250, 12.5),
+            (Moment::Reflectivity, 32768 + 2550, 25.5),
+            (Moment::Velocity, 32768 - 1000, -10.0),
+            (Moment::Zdr, 32768 + 150, 1.5),
+            (Moment::Kdp, 32768 + 50, 0.5),
+            (Moment::Phi, 32768 + 16384, 16384.0 * 360.0 / 65535.0),
+            (Moment::Rho, 32768, 1.0),
+            (Moment::SpectrumWidth, 101, 1.0),
+            (Moment::Quality, 7, 7.0),
+        ];
+        assert_eq!(pairs.map(|(m, _, _)| m), Moment::ALL);
+        for (moment, raw, physical) in pairs {
+            let decoded = moment.decode(raw);
+            assert!(
+                (decoded - physical).abs() < 1e-9,
+                "{moment} {raw} is {decoded}"
+            );
+        }
+    }
+
+    #[test]
+    fn no_data_code_decodes_to_nan() {
+        for moment in Moment::ALL {
+            match moment.info().no_data {
+                Some(code) => assert!(moment.decode(code).is_nan(), "{moment}"),
+                None => assert_eq!(moment.decode(0), 0.0),
+            }
+        }
+        assert_eq!(Moment::Quality.info().no_data, None);
+    }
+
+    #[test]
+    fn spectrum_width_is_never_negative() {
+        let sw = Moment::SpectrumWidth;
+        let shifted = Calibration {
+            offset: 50.0,
+            scale: 0.01,
+        };
+        for raw in 0..=200 {
+            for (calibration, no_data) in [(sw.calibration(), None), (shifted, Some(0))] {
+                let value = sw.clamp(calibration.decode(raw, no_data));
+                assert!(value.is_nan() || value >= 0.0, "{raw} is {value}");
+            }
+        }
+        assert_eq!(sw.clamp(sw.calibration().decode(0, None)), 0.0);
+        assert_eq!(sw.decode(1), 0.0);
+    }
+}
@@ -0,0 +1,240 @@
+use crate::{Calibration, Moment, ObservationMode, RhixHeader};
+use hdf5::types::VarLenAscii;
+use std::collections::HashMap;
+
+/// Writes an ODIM_H5 2.2 polar volume, or a single scan when `radar` has one sweep.
+///
+/// Every sweep is a `datasetN` group and every moment a `dataM` group within it, numbered from
+/// 1 in sweep order and moment name order. Moments are stored as the `uint16` counts they were
+/// recorded as, re-encoded with `calibration` or the built-in scaling, with `gain` and `offset`
+/// set so `gain * raw + offset` gives back the physical value. NaN is stored as 0, the `nodata`
+/// and `undetect` code. Moments that are not under their own name, such as after a rename,
+/// are stored as 64 bit floats with NaN as `nodata`.
+///
+/// Rows are ordered by azimuth as ODIM expects, with `a1gate` the row of the first ray
+/// recorded. Only PPI scans are supported.
+pub fn write_odim(
+    radar: &silv::RadarFile,
+    header: &RhixHeader,
+    calibration: &HashMap<Moment, Calibration>,
+    path: impl AsRef<std::path::Path>,
+) -> Result<(), hdf5::Error> {
+    if header.geometry.observation_mode != ObservationMode::Ppi {
+        return Err("ODIM output only supports PPI scans".into());
+    }
+
+    let file = hdf5::File::create(path)?;
+    put_str(&file, "Conventions", "ODIM_H5/V2_2")?;
+
+    let rays = radar.sweeps.iter().flat_map(|s| &s.rays);
+    let (start, end) = (
+        rays.clone().map(|r| r.time).min(),
+        rays.map(|r| r.time).max(),
+    );
+
+    let what = file.create_group("what")?;
+    let object = match radar.sweeps.len() {
+        1 => "SCAN",
+        _ => "PVOL",
+    };
+    put_str(&what, "object", object)?;
+    put_str(&what, "version", "H5rad 2.2")?;
+    put_str(&what, "source", &format!("PLC:{}", radar.name))?;
+    if let Some(start) = start {
+        put_str(&what, "date", &start.format("%Y%m%d").to_string())?;
+        put_str(&what, "time", &start.format("%H%M%S").to_string())?;
+    }
+
+    if let Some(site) = radar.sweeps.first() {
+        let location = file.create_group("where")?;
+        put(&location, "lat", site.latitude as f64)?;
+        put(&location, "lon", site.longitude as f64)?;
+        put(&location, "height", header.site.altitude as f64)?;
+    }
+
+    let how = file.create_group("how")?;
+    put(&how, "beamwH", header.calibration.beamwidth_h as f64)?;
+    put(&how, "beamwV", header.calibration.beamwidth_v as f64)?;
+    put_str(&how, "software", "rhix_converter")?;
+    if let (Some(start), Some(end)) = (start, end) {
+        put(&how, "startepochs", start.timestamp() as f64)?;
+        put(&how, "endepochs", end.timestamp() as f64)?;
+    }
+
+    let mut moments: Vec<_> = radar.params.keys().collect();
+    moments.sort();
+
+    for (i, sweep) in radar.sweeps.iter().enumerate() {
+        let dataset = file.create_group(&format!("dataset{}", i + 1))?;
+        let gates = sweep
+            .rays
+            .iter()
+            .flat_map(|r| r.data.values().map(Vec::len))
+            .max()
+            .unwrap_or(0);
+
+        let mut order: Vec<_> = (0..sweep.rays.len()).collect();
+        order.sort_by(|&a, &b| sweep.rays[a].azimuth.total_cmp(&sweep.rays[b].azimuth));
+        let a1gate = order.iter().position(|&ray| ray == 0).unwrap_or(0);
+
+        let what = dataset.create_group("what")?;
+        put_str(&what, "product", "SCAN")?;
+        let start = sweep.rays.iter().map(|r| r.time).min();
+        let end = sweep.rays.iter().map(|r| r.time).max();
+        if let (Some(start), Some(end)) = (start, end) {
+            put_str(&what, "startdate", &start.format("%Y%m%d").to_string())?;
+            put_str(&what, "starttime", &start.format("%H%M%S").to_string())?;
+            put_str(&what, "enddate", &end.format("%Y%m%d").to_string())?;
+            put_str(&what, "endtime", &end.format("%H%M%S").to_string())?;
+        }
+
+        let location = dataset.create_group("where")?;
+        put(&location, "elangle", sweep.elevation as f64)?;
+        put(&location, "nbins", gates as i64)?;
+        put(&location, "nrays", sweep.rays.len() as i64)?;
+        put(&location, "a1gate", a1gate as i64)?;
+        if let Some(param) = moments.first().map(|name| &radar.params[name.as_str()]) {
+            put(
+                &location,
+                "rstart",
+                param.meters_to_first_cell as f64 / 1000.0,
+            )?;
+            put(&location, "rscale", param.meters_between_cells as f64)?;
+        }
+
+        // Each ray is taken to span an equal share of the circle around its azimuth
+        let half_width = 180.0 / sweep.rays.len().max(1) as f64;
+        let azimuths: Vec<f64> = order
+            .iter()
+            .map(|&r| sweep.rays[r].azimuth as f64)
+            .collect();
+        let start_azimuths: Vec<f64> = azimuths
+            .iter()
+            .map(|a| (a - half_width).rem_euclid(360.0))
+            .collect();
+        let stop_azimuths: Vec<f64> = azimuths
+            .iter()
+            .map(|a| (a + half_width).rem_euclid(360.0))
+            .collect();
+        let times: Vec<f64> = order
+            .iter()
+            .map(|&r| sweep.rays[r].time.timestamp_millis() as f64 / 1000.0)
+            .collect();
+
+        let how = dataset.create_group("how")?;
+        put(&how, "NI", sweep.nyquist_velocity as f64)?;
+        put_array(&how, "startazA", &start_azimuths)?;
+        put_array(&how, "stopazA", &stop_azimuths)?;
+        put_array(&how, "startazT", &times)?;
+        put_array(&how, "stopazT", &times)?;
+
+        for (j, &name) in moments.iter().enumerate() {
+            let rows = order.iter().map(|&r| sweep.rays[r].data.get(name.as_str()));
+            let data = dataset.create_group(&format!("data{}", j + 1))?;
+            let what = data.create_group("what")?;
+
+            let Some(moment) = Moment::from_name(name) else {
+                let mut values = vec![f64::NAN; sweep.rays.len() * gates];
+                for (row, ray) in values.chunks_exact_mut(gates.max(1)).zip(rows) {
+                    row.iter_mut()
+                        .zip(ray.into_iter().flatten())
+                        .for_each(|(v, d)| *v = *d);
+                }
+
+                put_str(&what, "quantity", name)?;
+                put(&what, "gain", 1.0)?;
+                put(&what, "offset", 0.0)?;
+                put(&what, "nodata", f64::NAN)?;
+                put(&what, "undetect", f64::NAN)?;
+                write_image(&data, &values, sweep.rays.len(), gates)?;
+                continue;
+            };
+
+            let scaling = calibration
+                .get(&moment)
+                .copied()
+                .unwrap_or(moment.calibration());
+            // Raw 0 stays free for NaN, unless the moment has no no-data code of its own
+            let lowest = match moment.info().no_data {
+                Some(_) => 1.0,
+                None => 0.0,
+            };
+            let mut values = vec![NODATA; sweep.rays.len() * gates];
+            for (row, ray) in values.chunks_exact_mut(gates.max(1)).zip(rows) {
+                for (v, &d) in row.iter_mut().zip(ray.into_iter().flatten()) {
+                    if !d.is_nan() {
+                        let raw = d / scaling.scale + scaling.offset;
+                        *v = raw.round().clamp(lowest, u16::MAX as f64) as u16;
+                    }
+                }
+            }
+
+            put_str(&what, "quantity", quantity(moment))?;
+            put(&what, "gain", scaling.scale)?;
+            put(&what, "offset", -scaling.offset * scaling.scale)?;
+            put(&what, "nodata", NODATA as f64)?;
+            put(&what, "undetect", NODATA as f64)?;
+            write_image(&data, &values, sweep.rays.len(), gates)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Raw count written for gates without a value.
+const NODATA: u16 = 0;
+
+/// ODIM quantity of a moment.
+///
+/// The quality channel has no documented scaling, so it keeps its own name rather than
+/// passing for the 0 to 1 `QIND`.
+fn quantity(moment: Moment) -> &'static str {
+    match moment {
+        Moment::RainRate => "RATE",
+        Moment::Reflectivity => "DBZH",
+        Moment::Velocity => "VRADH",
+        Moment::Zdr => "ZDR",
+        Moment::Kdp => "KDP",
+        Moment::Phi => "PHIDP",
+        Moment::Rho => "RHOHV",
+        Moment::SpectrumWidth => "WRADH",
+        Moment::Quality => "QC",
+    }
+}
+
+/// Writes `values` as the `data` image of a data group, `rays` rows of `gates` bins.
+fn write_image<T: hdf5::H5Type>(
+    group: &hdf5::Group,
+    values: &[T],
+    rays: usize,
+    gates: usize,
+) -> Result<(), hdf5::Error> {
+    let data = group
+        .new_dataset::<T>()
+        .shape((rays, gates))
+        .create("data")?;
+    data.write_raw(values)?;
+    put_str(&data, "CLASS", "IMAGE")?;
+    put_str(&data, "IMAGE_VERSION", "1.2")
+}
+
+fn put<T: hdf5::H5Type>(
+    location: &hdf5::Location,
+    name: &str,
+    value: T,
+) -> Result<(), hdf5::Error> {
+    location.new_attr::<T>().create(name)?.write_scalar(&value)
+}
+
+fn put_array(location: &hdf5::Location, name: &str, values: &[f64]) -> Result<(), hdf5::Error> {
+    location
+        .new_attr::<f64>()
+        .shape(values.len())
+        .create(name)?
+        .write_raw(values)
+}
+
+fn put_str(location: &hdf5::Location, name: &str, value: &str) -> Result<(), hdf5::Error> {
+    let value = VarLenAscii::from_ascii(value).map_err(|e| e.to_string())?;
+    put(location, name, value)
+}
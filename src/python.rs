@@ -0,0 +1,65 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// Parses an uncompressed `.rhix` buffer into a dict of `name`, `header` and `sweeps`.
+///
+/// `header` holds every header field under its Rust name, nested in the same groups as
+/// `RhixHeader`. Each sweep has its `elevation` and `nyquist_velocity`, per ray `azimuth` and
+/// `time` lists, with times in milliseconds since the Unix epoch, and a `moments` dict of ray
+/// by gate lists ready for `numpy.array`.
+#[pyfunction]
+fn read_rhix<'py>(py: Python<'py>, data: &[u8]) -> PyResult<Bound<'py, PyDict>> {
+    let error = |e: &dyn std::fmt::Display| PyValueError::new_err(e.to_string());
+    let (header, radar) = crate::read_rhix(data).map_err(|e| error(&e))?;
+
+    // Going through JSON keeps the field names and enum values the same as --dump-header
+    let header = serde_json::to_string(&header).map_err(|e| error(&e))?;
+    let header = py.import_bound("json")?.call_method1("loads", (header,))?;
+
+    let mut moments: Vec<_> = radar.params.keys().collect();
+    moments.sort();
+
+    let sweeps = radar
+        .sweeps
+        .iter()
+        .map(|sweep| {
+            let dict = PyDict::new_bound(py);
+            dict.set_item("elevation", sweep.elevation)?;
+            dict.set_item("nyquist_velocity", sweep.nyquist_velocity)?;
+
+            let azimuths: Vec<_> = sweep.rays.iter().map(|r| r.azimuth).collect();
+            dict.set_item("azimuth", azimuths)?;
+            let times: Vec<_> = sweep
+                .rays
+                .iter()
+                .map(|r| r.time.timestamp_millis())
+                .collect();
+            dict.set_item("time", times)?;
+
+            let data = PyDict::new_bound(py);
+            for &moment in &moments {
+                let values: Vec<_> = sweep
+                    .rays
+                    .iter()
+                    .map(|r| r.data.get(moment).cloned().unwrap_or_default())
+                    .collect();
+                data.set_item(moment, values)?;
+            }
+            dict.set_item("moments", data)?;
+
+            Ok(dict)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let dict = PyDict::new_bound(py);
+    dict.set_item("name", radar.name)?;
+    dict.set_item("header", header)?;
+    dict.set_item("sweeps", sweeps)?;
+    Ok(dict)
+}
+
+#[pymodule]
+fn rhix_converter(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(read_rhix, m)?)
+}
@@ -0,0 +1,95 @@
+use crate::{Moment, RhixHeader};
+
+/// Default SNR threshold in dB, the gap between the short pulse threshold and noise powers.
+///
+/// Header powers are stored in hundredths of a dB(m).
+pub fn default_snr_threshold(header: &RhixHeader) -> f64 {
+    (header.calibration.thresh_power_short as f64 - header.calibration.noise_power_h_short as f64)
+        / 100.0
+}
+
+/// Masks reflectivity gates whose signal to noise ratio is below `threshold` dB with NaN.
+///
+/// The received power is recovered from reflectivity with the horizontal radar constant and
+/// the range of each gate, then compared to the short pulse noise power. With `all_moments`
+/// every other moment is masked at the same gates.
+pub fn mask_low_snr(
+    sweep: &mut silv::Sweep,
+    header: &RhixHeader,
+    threshold: f64,
+    all_moments: bool,
+) {
+    let radar_const = header.calibration.radar_const_h as f64 / 100.0;
+    let noise = header.calibration.noise_power_h_short as f64 / 100.0;
+    let first = header.pulse.tx_pulse_blind_len as f64;
+    let res = header.geometry.gate_res as f64;
+
+    for ray in &mut sweep.rays {
+        let Some(reflectivity) = ray.data.get_mut(Moment::Reflectivity.name()) else {
+            continue;
+        };
+
+        let mut masked = Vec::new();
+        for (i, z) in reflectivity.iter_mut().enumerate() {
+            let range_km = (first + (i as f64 + 0.5) * res) / 1000.0;
+            let power = *z - radar_const - 20.0 * range_km.log10();
+            if power - noise < threshold {
+                *z = f64::NAN;
+                masked.push(i);
+            }
+        }
+
+        if all_moments {
+            for data in ray.data.values_mut() {
+                for &i in &masked {
+                    if let Some(v) = data.get_mut(i) {
+                        *v = f64::NAN;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SyntheticScan;
+
+    #[test]
+    fn default_threshold_is_threshold_over_noise() {
+        let scan = SyntheticScan {
+            noise_power_h_short: -500,
+            thresh_power_short: 1000,
+            ..Default::default()
+        };
+        let (header, _) = crate::read_rhix(&scan.to_bytes()).unwrap();
+        assert_eq!(default_snr_threshold(&header), 15.0);
+    }
+
+    #[test]
+    fn weak_gates_are_masked() {
+        let scan = SyntheticScan {
+            rays: 1,
+            gates: 3,
+            moments: vec![Moment::Reflectivity, Moment::Velocity],
+            ..Default::default()
+        };
+        // With a radar constant and noise power of 0, 0 dBZ at the first gate center 25 m out
+        // is 32 dB above noise, and 50 dBZ at the second, 75 m out, is 72 dB above it
+        let bytes = scan.to_bytes_with(|_, _, gate| match gate {
+            0 => 32768,
+            _ => 32768 + 5000,
+        });
+
+        for all_moments in [false, true] {
+            let (header, mut radar) = crate::read_rhix(&bytes).unwrap();
+            mask_low_snr(&mut radar.sweeps[0], &header, 40.0, all_moments);
+            let data = &radar.sweeps[0].rays[0].data;
+            let reflectivity = &data[Moment::Reflectivity.name()];
+            assert!(reflectivity[0].is_nan());
+            assert_eq!(reflectivity[1..], [50.0, 50.0]);
+            assert_eq!(data[Moment::Velocity.name()][0].is_nan(), all_moments);
+        }
+    }
+}
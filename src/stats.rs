@@ -0,0 +1,52 @@
+use crate::Moment;
+use serde::Serialize;
+
+/// Value statistics of one moment over every gate of a radar file.
+#[derive(Debug, Clone, Serialize)]
+pub struct MomentStats {
+    pub moment: &'static str,
+    pub units: &'static str,
+    /// Gates with a value.
+    pub valid: usize,
+    /// Gates that decoded to NaN, from a no-data code or masking.
+    pub missing: usize,
+    /// `None` when no gate is valid, like the mean.
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub mean: Option<f64>,
+}
+
+/// Statistics of every moment in `radar`, in [`Moment::ALL`] order.
+pub fn moment_stats(radar: &silv::RadarFile) -> Vec<MomentStats> {
+    Moment::ALL
+        .into_iter()
+        .filter(|moment| radar.params.contains_key(moment.name()))
+        .map(|moment| {
+            let mut stats = MomentStats {
+                moment: moment.name(),
+                units: moment.units(),
+                valid: 0,
+                missing: 0,
+                min: None,
+                max: None,
+                mean: None,
+            };
+
+            let mut sum = 0.0;
+            let rays = radar.sweeps.iter().flat_map(|s| &s.rays);
+            for &v in rays.filter_map(|r| r.data.get(moment.name())).flatten() {
+                if v.is_nan() {
+                    stats.missing += 1;
+                    continue;
+                }
+                stats.valid += 1;
+                sum += v;
+                stats.min = Some(stats.min.map_or(v, |min| min.min(v)));
+                stats.max = Some(stats.max.map_or(v, |max| max.max(v)));
+            }
+            stats.mean = (stats.valid > 0).then(|| sum / stats.valid as f64);
+
+            stats
+        })
+        .collect()
+}
@@ -0,0 +1,87 @@
+use crate::RhixHeader;
+
+/// Groups single-tilt scans into volumes and merges each volume into one radar file.
+///
+/// Scans are taken in start time order. A scan joins the current volume when it has the same
+/// `total_scans`, a `scan_num` the volume does not have yet, and starts within `max_gap` of the
+/// previous scan. Sweeps of a volume are ordered by `scan_num` and the volume is named after its
+/// first scan. The header of that first scan is returned alongside it.
+pub fn merge_volumes(
+    mut scans: Vec<(RhixHeader, silv::RadarFile)>,
+    max_gap: chrono::Duration,
+) -> Vec<(RhixHeader, silv::RadarFile)> {
+    let start = |header: &RhixHeader| {
+        header
+            .utc_offset()
+            .and_then(|offset| header.timing.start_time.to_utc(offset))
+            .ok()
+    };
+    scans.sort_by_key(|(header, _)| start(header));
+
+    let mut volumes: Vec<Vec<(RhixHeader, silv::RadarFile)>> = Vec::new();
+    for (header, radar) in scans {
+        let joins = volumes.last().is_some_and(|volume| {
+            let (last, _) = volume.last().expect("volumes are never empty");
+            let close = match (start(last), start(&header)) {
+                (Some(last), Some(next)) => next - last <= max_gap,
+                _ => false,
+            };
+
+            close
+                && last.geometry.total_scans == header.geometry.total_scans
+                && volume
+                    .iter()
+                    .all(|(h, _)| h.geometry.scan_num != header.geometry.scan_num)
+        });
+
+        match volumes.last_mut() {
+            Some(volume) if joins => volume.push((header, radar)),
+            _ => volumes.push(vec![(header, radar)]),
+        }
+    }
+
+    volumes.into_iter().map(merge).collect()
+}
+
+fn merge(mut volume: Vec<(RhixHeader, silv::RadarFile)>) -> (RhixHeader, silv::RadarFile) {
+    volume.sort_by_key(|(header, _)| header.geometry.scan_num);
+
+    let mut scans = volume.into_iter();
+    let (header, mut radar) = scans.next().expect("volumes are never empty");
+    for (_, scan) in scans {
+        radar.sweeps.extend(scan.sweeps);
+        for (name, param) in scan.params {
+            radar.params.entry(name).or_insert(param);
+        }
+    }
+
+    (header, radar)
+}
+
+/// Splits a radar file into one file per sweep, named `<name>_<index>` with a two digit index.
+///
+/// Every file keeps the full set of moment descriptions. Site metadata is part of each sweep.
+pub fn split_sweeps(radar: silv::RadarFile) -> Vec<silv::RadarFile> {
+    let params = &radar.params;
+    radar
+        .sweeps
+        .into_iter()
+        .enumerate()
+        .map(|(i, sweep)| silv::RadarFile {
+            name: format!("{}_{i:02}", radar.name),
+            sweeps: vec![sweep],
+            params: params
+                .iter()
+                .map(|(name, param)| {
+                    let param = silv::ParamDescription {
+                        description: param.description.clone(),
+                        units: param.units.clone(),
+                        meters_to_first_cell: param.meters_to_first_cell,
+                        meters_between_cells: param.meters_between_cells,
+                    };
+                    (name.clone(), param)
+                })
+                .collect(),
+        })
+        .collect()
+}
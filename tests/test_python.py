@@ -0,0 +1,37 @@
+"""Reads a one ray scan through the Python module, after `maturin develop`."""
+
+import math
+import struct
+
+import rhix_converter
+
+
+def scan_bytes():
+    """One ray of three reflectivity gates, the same bytes `SyntheticScan` writes for it."""
+    header = struct.pack("<HH", 156, 3)
+    for minute in (0, 1):
+        header += struct.pack("<H6B", 2023, 5, 1, 12, minute, 0, 0)
+    # Timezone, product number, model type, latitude, longitude, altitude and azimuth offset
+    header += struct.pack("<hHHiiiH", 0, 0, 0, 3_500_000, 13_500_000, 100, 0)
+    header += struct.pack("<I13H", 9_400_000, *[0] * 13)
+    header += struct.pack("<15H", 0, 0, 1000, 0, 0, 160, 32, 0, 0, 0, 0, 0, 0, 1, 30)
+    header += struct.pack("<5H", 1, 3, 50, 1, 1)
+    # Reflectivity only in record_item
+    header += struct.pack("<13HHH16x", *[0] * 13, 1 << 1, 0)
+    assert len(header) == 156
+
+    ray = struct.pack("<4H3H", 6, 0, 50, 2 + 3 * 2, 32768, 32768 + 1000, 0)
+    return header + ray
+
+
+def test_read_rhix():
+    scan = rhix_converter.read_rhix(scan_bytes())
+
+    assert scan["header"]["geometry"]["gates"] == 3
+    sweep = scan["sweeps"][0]
+    assert abs(sweep["elevation"] - 0.5) < 0.01
+    assert sweep["azimuth"] == [0.0]
+
+    reflectivity = sweep["moments"]["REF"]
+    assert reflectivity[0][:2] == [0.0, 10.0]
+    assert math.isnan(reflectivity[0][2])